@@ -0,0 +1,121 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, SocketAddr, SocketAddrV6};
+use std::str::FromStr;
+
+use serde_derive::Serialize;
+
+use crate::multicast::MulticastInterface;
+
+/// The local address to send from. A link-local IPv6 address needs the interface it is on as its scope,
+/// written after a `%` as the index or the name of the interface, e.g. `10.0.0.5`, `fe80::1%eth0` or `fe80::1%2`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BindAddress {
+    pub address: IpAddr,
+    pub scope: Option<BindScope>,  // only for IPv6 addresses
+}
+
+/// The scope of an IPv6 bind address: the interface, by index or by name.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum BindScope {
+    Index(u32),
+    Name(String),
+}
+
+impl BindAddress {
+    pub fn new(address: IpAddr) -> Self {
+        Self { address, scope: None }
+    }
+
+    pub fn with_scope(self, scope: BindScope) -> Self {
+        Self { scope: Some(scope), ..self }
+    }
+
+    /// The socket address to bind to, with the scope resolved to the index of its interface.
+    /// Without a scope of its own, a link-local address is scoped to `default_scope`, if any.
+    pub(crate) fn socket_address(&self, port: u16, default_scope: Option<&MulticastInterface>) -> Result<SocketAddr, std::io::Error> {
+        match self.address {
+            IpAddr::V4(_) => { Ok(SocketAddr::new(self.address, port)) }
+            IpAddr::V6(address) => {
+                let scope_id = match (&self.scope, default_scope) {
+                    (Some(BindScope::Index(index)), _) => { *index }
+                    (Some(BindScope::Name(name)), _) => { MulticastInterface::Name(name.clone()).index()? }
+                    (None, Some(interface)) if address.is_unicast_link_local() => { interface.index()? }
+                    (None, _) => { 0 }
+                };
+                Ok(SocketAddr::V6(SocketAddrV6::new(address, port, 0, scope_id)))
+            }
+        }
+    }
+}
+
+impl From<IpAddr> for BindAddress {
+    fn from(address: IpAddr) -> Self {
+        Self::new(address)
+    }
+}
+
+impl FromStr for BindAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (address, scope) = match s.split_once('%') {
+            Some((address, scope)) => { (address, Some(scope)) }
+            None => { (s, None) }
+        };
+        let address: IpAddr = address.parse()
+            .map_err(|_| format!("`{s}` is not an IP address"))?;
+        match (address, scope) {
+            (_, None) => { Ok(Self::new(address)) }
+            (IpAddr::V4(_), Some(_)) => { Err(format!("`{s}` has a scope, which only IPv6 addresses have")) }
+            (IpAddr::V6(_), Some("")) => { Err(format!("`{s}` has an empty scope")) }
+            (IpAddr::V6(_), Some(scope)) => {
+                let scope = scope.parse().map_or_else(|_| BindScope::Name(scope.to_string()), BindScope::Index);
+                Ok(Self::new(address).with_scope(scope))
+            }
+        }
+    }
+}
+
+impl Display for BindAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.address)?;
+        match &self.scope {
+            Some(BindScope::Index(index)) => { write!(f, "%{index}") }
+            Some(BindScope::Name(name)) => { write!(f, "%{name}") }
+            None => { Ok(()) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn parse_bind_address() {
+        let link_local = IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+        assert_eq!("10.0.0.5".parse(), Ok(BindAddress::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)))));
+        assert_eq!("fe80::1".parse(), Ok(BindAddress::new(link_local)));
+        assert_eq!("fe80::1%2".parse(), Ok(BindAddress::new(link_local).with_scope(BindScope::Index(2))));
+        assert_eq!("fe80::1%eth0".parse(), Ok(BindAddress::new(link_local).with_scope(BindScope::Name("eth0".to_string()))));
+        assert_eq!("fe80::1%eth0".parse::<BindAddress>().unwrap().to_string(), "fe80::1%eth0");
+        assert!("10.0.0.5%eth0".parse::<BindAddress>().is_err());
+        assert!("fe80::1%".parse::<BindAddress>().is_err());
+        assert!("eth0".parse::<BindAddress>().is_err());
+    }
+
+    #[test]
+    fn resolve_scope() {
+        let address: BindAddress = "fe80::1%7".parse().unwrap();
+        assert_eq!(address.socket_address(3000, None).unwrap(), "[fe80::1%7]:3000".parse().unwrap());
+        let address: BindAddress = "::1".parse().unwrap();
+        let interface = MulticastInterface::Name("no-such-if0".to_string());
+        assert_eq!(address.socket_address(3000, Some(&interface)).unwrap(), "[::1]:3000".parse().unwrap());
+        let address: BindAddress = "fe80::1".parse().unwrap();
+        assert!(address.socket_address(3000, Some(&interface)).is_err());
+        assert!("fe80::1%no-such-if0".parse::<BindAddress>().unwrap().socket_address(3000, None).is_err());
+    }
+}
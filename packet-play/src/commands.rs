@@ -1,4 +1,7 @@
 use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::defaults::DEFAULT_SPEED;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Command {
@@ -6,7 +9,12 @@ pub enum Command {
     Pause,
     Rewind,
     Quit,
-    Seek(usize),
+    Seek(usize),        // the packet index, starting at 0
+    SeekTime(Duration), // the time since the first packet
+    StepForward(usize), // send the next packets while paused
+    StepBackward(usize),// step back from the last sent packet and send it again, while paused
+    SetSpeed(f64),      // a multiplier of the recorded timing, which also ends unthrottled playback
+    SetUnthrottled,
 }
 
 impl Command {
@@ -17,18 +25,30 @@ impl Command {
             "Rewind",
             "Quit",
             "Seek",
+            "SeekTime",
+            "StepForward",
+            "StepBackward",
+            "SetSpeed",
+            "SetUnthrottled",
         ]
     }
 }
 
+/// The command at the index of its name in `as_vec`, with default arguments. Other indices quit.
 impl From<usize> for Command {
     fn from(value: usize) -> Self {
         match value {
             0 => { Command::Play }
             1 => { Command::Pause }
             2 => { Command::Rewind }
-            3 => { Command::Seek(0) }
-            4 | _ => { Command::Quit }
+            3 => { Command::Quit }
+            4 => { Command::Seek(0) }
+            5 => { Command::SeekTime(Duration::ZERO) }
+            6 => { Command::StepForward(1) }
+            7 => { Command::StepBackward(1) }
+            8 => { Command::SetSpeed(DEFAULT_SPEED) }
+            9 => { Command::SetUnthrottled }
+            _ => { Command::Quit }
         }
     }
 }
@@ -41,6 +61,24 @@ impl Display for Command {
             Command::Rewind => { write!(f, "Rewind") }
             Command::Quit => { write!(f, "Quit") }
             Command::Seek(_) => { write!(f, "Seek") }
+            Command::SeekTime(_) => { write!(f, "SeekTime") }
+            Command::StepForward(_) => { write!(f, "StepForward") }
+            Command::StepBackward(_) => { write!(f, "StepBackward") }
+            Command::SetSpeed(_) => { write!(f, "SetSpeed") }
+            Command::SetUnthrottled => { write!(f, "SetUnthrottled") }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_from_index_of_its_name() {
+        for (index, name) in Command::as_vec().into_iter().enumerate() {
+            assert_eq!(Command::from(index).to_string(), name);
         }
+        assert_eq!(Command::from(Command::as_vec().len()), Command::Quit);
     }
 }
@@ -1 +1,19 @@
+use std::time::Duration;
+
 pub const PLAYER_STARTUP_TIMEOUT_MS : u64 = 2000;
+
+/// The interval between position events during unthrottled playback.
+pub(crate) const UNTHROTTLED_POSITION_INTERVAL : Duration = Duration::from_millis(100);
+
+/// How often commands are handled while waiting for the next packet or iteration.
+pub(crate) const COMMAND_POLL_INTERVAL : Duration = Duration::from_millis(10);
+
+/// How long before a packet is due to stop sleeping and spin instead, as sleeping overshoots.
+pub(crate) const SPIN_THRESHOLD : Duration = Duration::from_millis(2);
+
+/// The interval between pacing events during playback.
+pub(crate) const PACING_REPORT_INTERVAL : Duration = Duration::from_secs(1);
+
+/// How often a failed send is retried under `SendFailurePolicy::Retry`, and the delay before each retry.
+pub(crate) const SEND_RETRIES : u32 = 3;
+pub(crate) const SEND_RETRY_DELAY : Duration = Duration::from_millis(1);
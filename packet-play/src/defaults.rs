@@ -1,3 +1,9 @@
 pub const DEFAULT_DEST_PORT : u16 = 3000;
 pub const DEFAULT_SRC_PORT : u16 = 33000;
-pub const DEFAULT_TTL : u32 = 1;
\ No newline at end of file
+pub const DEFAULT_TTL : u32 = 1;
+pub const DEFAULT_SPEED : f64 = 1.0;
+/// The playback speeds offered by the frontends to step through.
+pub const SPEED_STEPS : [f64; 11] = [0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 1000.0];
+/// The range of playback speeds the `Player` accepts; other speeds are clamped to it.
+pub const MIN_SPEED : f64 = SPEED_STEPS[0];
+pub const MAX_SPEED : f64 = SPEED_STEPS[SPEED_STEPS.len() - 1];
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use serde_derive::Serialize;
+
+/// How the `Player` chooses the destination of each replayed datagram.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+pub enum DestinationMode {
+    #[default]
+    Fixed,          // send everything to the configured destination
+    OriginalPort,   // send to the configured destination address, on the captured destination port
+    Original,       // send to the captured destination address and port
+}
+
+impl Display for DestinationMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DestinationMode::Fixed => { write!(f, "fixed") }
+            DestinationMode::OriginalPort => { write!(f, "original port") }
+            DestinationMode::Original => { write!(f, "original address and port") }
+        }
+    }
+}
+
+/// Rewrites a captured destination to a new one, written as `ORIGINAL=NEW`, e.g. `239.1.2.3:3001=10.0.0.5:4001`.
+/// Mappings take precedence over the `DestinationMode`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DestinationMapping {
+    pub original: SocketAddr,
+    pub replacement: SocketAddr,
+}
+
+impl FromStr for DestinationMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (original, replacement) = s.split_once('=')
+            .ok_or_else(|| format!("Expected ORIGINAL=NEW, found `{s}`"))?;
+        let parse = |addr: &str| addr.trim().parse::<SocketAddr>()
+            .map_err(|err| format!("Invalid socket address `{addr}`: {err}"));
+        Ok(Self {
+            original: parse(original)?,
+            replacement: parse(replacement)?,
+        })
+    }
+}
+
+impl Display for DestinationMapping {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.original, self.replacement)
+    }
+}
+
+/// Resolves the destination of each replayed datagram from its captured destination.
+#[derive(Clone, Debug)]
+pub(crate) struct Destinations {
+    destination: SocketAddr,
+    mode: DestinationMode,
+    mappings: HashMap<SocketAddr, SocketAddr>,
+}
+
+impl Destinations {
+    pub(crate) fn new(destination: SocketAddr, mode: DestinationMode, mappings: &[DestinationMapping]) -> Self {
+        Self {
+            destination,
+            mode,
+            mappings: mappings.iter().map(|mapping| (mapping.original, mapping.replacement)).collect(),
+        }
+    }
+
+    pub(crate) fn resolve(&self, original: SocketAddr) -> SocketAddr {
+        if let Some(replacement) = self.mappings.get(&original) {
+            return *replacement;
+        }
+        match self.mode {
+            DestinationMode::Fixed => { self.destination }
+            DestinationMode::OriginalPort => { SocketAddr::new(self.destination.ip(), original.port()) }
+            DestinationMode::Original => { original }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_per_mode_with_mappings() {
+        let destination: SocketAddr = "192.168.1.255:3000".parse().unwrap();
+        let mappings = ["239.1.2.3:3001=10.0.0.5:4001".parse::<DestinationMapping>().unwrap()];
+        let dis: SocketAddr = "239.1.2.3:3000".parse().unwrap();
+        let voice: SocketAddr = "239.1.2.3:3001".parse().unwrap();
+
+        let fixed = Destinations::new(destination, DestinationMode::Fixed, &[]);
+        assert_eq!(fixed.resolve(voice), destination);
+        let ports = Destinations::new(destination, DestinationMode::OriginalPort, &mappings);
+        assert_eq!(ports.resolve(dis), "192.168.1.255:3000".parse().unwrap());
+        assert_eq!(ports.resolve(voice), mappings[0].replacement);
+        let original = Destinations::new(destination, DestinationMode::Original, &mappings);
+        assert_eq!(original.resolve(dis), dis);
+
+        assert!("239.1.2.3:3001".parse::<DestinationMapping>().is_err());
+        assert!("239.1.2.3=10.0.0.5:4001".parse::<DestinationMapping>().is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use std::time::Duration;
-use crate::player::PlayerState;
+use crate::player::{PlaybackSpeed, PlayerState};
+use crate::pacing::PacingReport;
+use crate::repeat::Repetitions;
 use crate::PlayerError;
 
 use serde_derive::Serialize;
@@ -10,6 +12,8 @@ pub enum Event {
     PlayerReady,
     PlayerStateChanged(StateChange),
     PlayerPositionChanged(PositionChange),
+    PlayerIterationChanged(IterationChange),
+    PlayerPacingChanged(PacingReport),
     QuitCommanded,
 }
 
@@ -20,13 +24,25 @@ impl Event {
         })
     }
 
-    pub(crate) fn position_event(current_pos: usize, max_pos: usize, current_time:Duration, total_time: Duration) -> Self {
+    pub(crate) fn position_event(current_pos: usize, max_pos: usize, current_time:Duration, total_time: Duration, speed: PlaybackSpeed) -> Self {
         // Note: This function increases the position with +1 to compensate for 0-based vec indexing.
         Event::PlayerPositionChanged(PositionChange{
             position: current_pos + 1,
             max_position: max_pos,
             time_position: current_time,
             time_total: total_time,
+            speed,
+            time_remaining: match speed {
+                PlaybackSpeed::Multiplier(_) => { Some(speed.scale(total_time.saturating_sub(current_time))) }
+                PlaybackSpeed::Unthrottled => { None }
+            },
+        })
+    }
+
+    pub(crate) fn iteration_event(iteration: u32, repetitions: Repetitions) -> Self {
+        Event::PlayerIterationChanged(IterationChange {
+            iteration,
+            repetitions,
         })
     }
 
@@ -46,6 +62,23 @@ pub struct PositionChange {
     pub max_position: usize,
     pub time_position: Duration,
     pub time_total: Duration,
+    pub speed: PlaybackSpeed,
+    pub time_remaining: Option<Duration>,   // the real time until the end at the current speed, if throttled
+}
+
+#[derive(Copy, Clone, Debug, Serialize)]
+pub struct IterationChange {
+    pub iteration: u32,     // starting at 1
+    pub repetitions: Repetitions,
+}
+
+impl Default for IterationChange {
+    fn default() -> Self {
+        Self {
+            iteration: 1,
+            repetitions: Repetitions::Once,
+        }
+    }
 }
 
 impl Default for PositionChange {
@@ -55,6 +88,8 @@ impl Default for PositionChange {
             max_position: 0,
             time_position: Duration::from_secs(0),
             time_total: Duration::from_secs(0),
+            speed: PlaybackSpeed::default(),
+            time_remaining: None,
         }
     }
 }
\ No newline at end of file
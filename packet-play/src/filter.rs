@@ -0,0 +1,239 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use pcap_files::IP_PROTOCOL_UDP;
+
+/// Selects the packets of a recording to replay, written as comma-separated terms that must all match,
+/// e.g. `src=10.0.0.1,dport=3000,time=10..60`. Ranges are written as `MIN..MAX`, `MIN..` or `..MAX`
+/// and include their bounds.
+///
+/// | Term            | Matches                                                                |
+/// |-----------------|------------------------------------------------------------------------|
+/// | `src=IP`        | the source address                                                     |
+/// | `dst=IP`        | the destination address                                                |
+/// | `host=IP`       | the source or destination address                                      |
+/// | `sport=PORT`    | the UDP source port                                                    |
+/// | `dport=PORT`    | the UDP destination port                                               |
+/// | `port=PORT`     | the UDP source or destination port                                     |
+/// | `proto=PROTO`   | the IP protocol, by number or as `udp`, `tcp` or `icmp`                |
+/// | `len=RANGE`     | the length of the IP payload, in bytes                                 |
+/// | `time=RANGE`    | the time since the first packet of the recording, in seconds           |
+/// | `index=RANGE`   | the packet number in the recording, starting at 1                      |
+///
+/// Fragmented datagrams are matched once reassembled, with the number and timestamp of their last fragment.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PacketFilter {
+    terms: Vec<FilterTerm>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum FilterTerm {
+    Source(IpAddr),
+    Destination(IpAddr),
+    Host(IpAddr),
+    SourcePort(u16),
+    DestinationPort(u16),
+    Port(u16),
+    Protocol(u8),
+    Length(Bounds<usize>),
+    Time(Bounds<Duration>),
+    Index(Bounds<usize>),
+}
+
+/// An inclusive range that may be open at either end.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Bounds<T> {
+    min: Option<T>,
+    max: Option<T>,
+}
+
+/// The decoded headers of a packet, as matched by a `PacketFilter`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct PacketInfo {
+    pub index: usize,               // starting at 0
+    pub time: Duration,             // since the first packet of the recording
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    pub protocol: u8,
+    pub ports: Option<(u16, u16)>,  // source and destination port, for UDP
+    pub length: usize,
+}
+
+impl PacketFilter {
+    /// Whether the filter selects all packets.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub(crate) fn matches(&self, packet: &PacketInfo) -> bool {
+        self.terms.iter().all(|term| term.matches(packet))
+    }
+}
+
+impl FilterTerm {
+    fn matches(&self, packet: &PacketInfo) -> bool {
+        match self {
+            FilterTerm::Source(ip) => { packet.source == *ip }
+            FilterTerm::Destination(ip) => { packet.destination == *ip }
+            FilterTerm::Host(ip) => { packet.source == *ip || packet.destination == *ip }
+            FilterTerm::SourcePort(port) => { packet.ports.is_some_and(|(source, _)| source == *port) }
+            FilterTerm::DestinationPort(port) => { packet.ports.is_some_and(|(_, destination)| destination == *port) }
+            FilterTerm::Port(port) => {
+                packet.ports.is_some_and(|(source, destination)| source == *port || destination == *port)
+            }
+            FilterTerm::Protocol(protocol) => { packet.protocol == *protocol }
+            FilterTerm::Length(bounds) => { bounds.contains(&packet.length) }
+            FilterTerm::Time(bounds) => { bounds.contains(&packet.time) }
+            FilterTerm::Index(bounds) => { bounds.contains(&(packet.index + 1)) }
+        }
+    }
+}
+
+impl<T: PartialOrd> Bounds<T> {
+    fn contains(&self, value: &T) -> bool {
+        self.min.as_ref().is_none_or(|min| value >= min) && self.max.as_ref().is_none_or(|max| value <= max)
+    }
+}
+
+impl FromStr for PacketFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let terms = s.split(',')
+            .map(str::trim)
+            .filter(|term| !term.is_empty())
+            .map(FilterTerm::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { terms })
+    }
+}
+
+impl FromStr for FilterTerm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s.split_once('=')
+            .ok_or_else(|| format!("Expected KEY=VALUE, found `{s}`"))?;
+        let value = value.trim();
+        match key.trim() {
+            "src" => { parse_value(value).map(FilterTerm::Source) }
+            "dst" => { parse_value(value).map(FilterTerm::Destination) }
+            "host" => { parse_value(value).map(FilterTerm::Host) }
+            "sport" => { parse_value(value).map(FilterTerm::SourcePort) }
+            "dport" => { parse_value(value).map(FilterTerm::DestinationPort) }
+            "port" => { parse_value(value).map(FilterTerm::Port) }
+            "proto" => {
+                match value {
+                    "icmp" => { Ok(FilterTerm::Protocol(1)) }
+                    "tcp" => { Ok(FilterTerm::Protocol(6)) }
+                    "udp" => { Ok(FilterTerm::Protocol(IP_PROTOCOL_UDP)) }
+                    _ => { parse_value(value).map(FilterTerm::Protocol) }
+                }
+            }
+            "len" => { Bounds::parse(value, parse_value).map(FilterTerm::Length) }
+            "time" => { Bounds::parse(value, parse_seconds).map(FilterTerm::Time) }
+            "index" => { Bounds::parse(value, parse_value).map(FilterTerm::Index) }
+            other => { Err(format!("Unknown filter term `{other}`")) }
+        }
+    }
+}
+
+impl<T: Copy> Bounds<T> {
+    /// Parses `MIN..MAX`, `MIN..`, `..MAX`, or a single value matching only itself.
+    fn parse(s: &str, parse: fn(&str) -> Result<T, String>) -> Result<Self, String> {
+        let parse_bound = |bound: &str| match bound.trim() {
+            "" => { Ok(None) }
+            bound => { parse(bound).map(Some) }
+        };
+        match s.split_once("..") {
+            Some((min, max)) => { Ok(Self { min: parse_bound(min)?, max: parse_bound(max)? }) }
+            None => {
+                let value = parse(s)?;
+                Ok(Self { min: Some(value), max: Some(value) })
+            }
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(s: &str) -> Result<T, String> where T::Err: Display {
+    s.parse().map_err(|err| format!("Invalid value `{s}`: {err}"))
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = parse_value(s)?;
+    Duration::try_from_secs_f64(seconds).map_err(|err| format!("Invalid time `{s}`: {err}"))
+}
+
+impl Display for PacketFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "all packets");
+        }
+        let terms: Vec<String> = self.terms.iter().map(FilterTerm::to_string).collect();
+        write!(f, "{}", terms.join(","))
+    }
+}
+
+impl Display for FilterTerm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterTerm::Source(ip) => { write!(f, "src={ip}") }
+            FilterTerm::Destination(ip) => { write!(f, "dst={ip}") }
+            FilterTerm::Host(ip) => { write!(f, "host={ip}") }
+            FilterTerm::SourcePort(port) => { write!(f, "sport={port}") }
+            FilterTerm::DestinationPort(port) => { write!(f, "dport={port}") }
+            FilterTerm::Port(port) => { write!(f, "port={port}") }
+            FilterTerm::Protocol(protocol) => { write!(f, "proto={protocol}") }
+            FilterTerm::Length(bounds) => { write!(f, "len={}", bounds.display(usize::to_string)) }
+            FilterTerm::Time(bounds) => {
+                write!(f, "time={}", bounds.display(|time| time.as_secs_f64().to_string()))
+            }
+            FilterTerm::Index(bounds) => { write!(f, "index={}", bounds.display(usize::to_string)) }
+        }
+    }
+}
+
+impl<T> Bounds<T> {
+    fn display(&self, to_string: impl Fn(&T) -> String) -> String {
+        let min = self.min.as_ref().map(&to_string).unwrap_or_default();
+        let max = self.max.as_ref().map(&to_string).unwrap_or_default();
+        format!("{min}..{max}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use super::*;
+
+    fn packet(index: usize, destination_port: u16) -> PacketInfo {
+        PacketInfo {
+            index,
+            time: Duration::from_secs(index as u64),
+            source: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            destination: IpAddr::V4(Ipv4Addr::new(239, 1, 2, 3)),
+            protocol: IP_PROTOCOL_UDP,
+            ports: Some((3000, destination_port)),
+            length: 100,
+        }
+    }
+
+    #[test]
+    fn parse_and_match_filter_terms() {
+        let filter: PacketFilter = "src=10.0.0.1, dport=3000, proto=udp, len=..200, time=1.5..".parse().unwrap();
+        assert!(filter.matches(&packet(2, 3000)));
+        assert!(!filter.matches(&packet(1, 3000)));
+        assert!(!filter.matches(&packet(2, 3001)));
+        assert_eq!(filter.to_string(), "src=10.0.0.1,dport=3000,proto=17,len=..200,time=1.5..");
+
+        let filter: PacketFilter = "host=239.1.2.3,index=3".parse().unwrap();
+        assert!(filter.matches(&packet(2, 3001)));
+        assert!(!filter.matches(&packet(3, 3001)));
+        assert!(PacketFilter::default().matches(&packet(0, 0)));
+
+        assert!("src=10.0.0".parse::<PacketFilter>().is_err());
+        assert!("vlan=10".parse::<PacketFilter>().is_err());
+    }
+}
@@ -3,27 +3,49 @@ mod commands;
 mod events;
 pub mod defaults;
 mod constants;
+mod recording;
+mod destination;
+mod filter;
+mod repeat;
+mod pacing;
+mod sinks;
+mod multicast;
+mod bind;
+mod raw;
 
-use std::ffi::OsStr;
-use std::fs::File;
 pub use commands::Command;
 pub use constants::*;
+pub use destination::{DestinationMapping, DestinationMode};
+pub use filter::PacketFilter;
+pub use repeat::{Repeat, RepeatPoint, RepeatSection, Repetitions};
+pub use pacing::PacingReport;
+pub use sinks::{file_sink, ChannelSink, MulticastSink, PcapNgSink, PcapSink, ReplayedPacket, TcpSink, UdpSink};
+pub use multicast::{MulticastInterface, MulticastOptions};
+pub use bind::{BindAddress, BindScope};
+pub use raw::{raw_sink, FrameRewrite, MacAddress};
+#[cfg(target_os = "linux")]
+pub use raw::RawSink;
+pub use packet_rehash_core::sink::{CapturedFrame, PacketSink, SinkPacket};
 pub use defaults::*;
 pub use events::Event;
 pub use events::PositionChange;
+pub use events::IterationChange;
 pub use events::StateChange;
 pub use player::Player;
 pub use player::PlayerState;
+pub use player::PlaybackSpeed;
+pub use player::SendFailurePolicy;
+pub use player::OutputStart;
+pub use recording::Recording;
 
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
 
 use clap::Parser;
-use log::error;
 use thiserror::Error;
+use pcap_files::{BpfFilter, PcapError};
 use serde_derive::Serialize;
 
-use pcap_files::{Pcap, PcapNG};
-
 #[derive(Parser, Debug)]
 // #[clap(name = "packet-play")]
 #[command(author, version, about, long_about = None)]
@@ -32,12 +54,71 @@ pub struct PlayerOptions {
     // #[arg(value_parser, try_from_str))]
     #[clap(short, long, default_value_t = SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), defaults::DEFAULT_DEST_PORT))]
     pub destination: SocketAddr,
+    /// Send each packet to its captured destination port, or captured destination address and port
+    #[clap(short = 'm', long = "mode", value_enum, default_value_t = DestinationMode::Fixed)]
+    pub destination_mode: DestinationMode,
+    /// Rewrite a captured destination to a new one, as ORIGINAL=NEW; can be repeated
+    #[clap(long = "map", value_name = "ORIGINAL=NEW")]
+    pub mappings: Vec<DestinationMapping>,
+    /// Replay only the packets matching all terms, e.g. src=10.0.0.1,dport=3000,time=10..60,index=1..500
+    /// (terms: src, dst, host, sport, dport, port, proto, len, time, index)
+    #[clap(short, long)]
+    pub filter: Option<PacketFilter>,
+    /// Replay only the packets matching a tcpdump filter expression, e.g. "udp dst port 3000 and not vlan"
+    #[clap(short, long)]
+    pub bpf: Option<BpfFilter>,
+    /// The local address to send from, with the scope of a link-local IPv6 address, e.g. fe80::1%eth0;
+    /// by default from both the IPv4 and the IPv6 unspecified address, matching the family of each destination
+    #[clap(long = "bind", value_name = "ADDR")]
+    pub bind_address: Option<BindAddress>,
     #[clap(short = 's', long = "source", default_value_t = defaults::DEFAULT_SRC_PORT)]
     pub source_port: u16,
+    /// The TTL of unicast and broadcast packets, and the TTL or hop limit of multicast packets
     #[clap(short, long, default_value_t = defaults::DEFAULT_TTL)]
     pub ttl: u32,
+    /// The interface to send multicast packets from, by address or name, e.g. 10.0.0.5 or eth1
+    #[clap(long = "multicast-if", value_name = "ADDR|NAME")]
+    pub multicast_interface: Option<MulticastInterface>,
+    /// Loop multicast packets back to the sending host
+    #[clap(long = "multicast-loop", value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    pub multicast_loopback: bool,
     #[clap(short, long)]
     pub auto_play_disable: bool,
+    /// Playback speed as a multiple of the recorded timing, from 0.1 to 1000, e.g. 10 or 0.5
+    #[clap(long, default_value_t = defaults::DEFAULT_SPEED, value_parser = parse_speed)]
+    pub speed: f64,
+    /// Replay without delays between packets, ignoring the speed
+    #[clap(short, long)]
+    pub unthrottled: bool,
+    /// Loop the playback N times, or forever when N is left out
+    #[clap(short, long = "loop", value_name = "N", num_args = 0..=1, default_missing_value = "0")]
+    pub loops: Option<u32>,
+    /// Repeat the section between two packet positions or times, e.g. 100..250 or 10s..25.5s; forever unless looped N times
+    #[clap(long = "ab", value_name = "A..B")]
+    pub section: Option<RepeatSection>,
+    /// The pause between iterations, in seconds
+    #[clap(long = "loop-gap", value_name = "SECONDS", default_value = "0", value_parser = parse_gap)]
+    pub loop_gap: Duration,
+    /// What to do when sending a packet fails
+    #[clap(long = "on-send-error", value_enum, default_value_t = SendFailurePolicy::Skip)]
+    pub send_failure: SendFailurePolicy,
+    /// Write the replayed packets to a .pcap or .pcapng file instead of sending them, without waiting,
+    /// stamped with the time each would have been sent
+    #[clap(short, long, value_name = "FILE")]
+    pub output: Option<String>,
+    /// The time the output file starts at: now, original, or seconds since the epoch
+    #[clap(long = "output-start", value_name = "START", default_value_t = OutputStart::Now)]
+    pub output_start: OutputStart,
+    /// Re-emit the captured Ethernet frames on the interface with their original headers, instead of sending
+    /// the payloads from a UDP socket; the destination options do not apply (Linux, needs CAP_NET_RAW)
+    #[clap(long, value_name = "INTERFACE", conflicts_with = "output")]
+    pub raw: Option<String>,
+    /// Rewrite a field of the re-emitted frames, as src-mac=MAC, dst-mac=MAC, src-ip=ADDR or dst-ip=ADDR; can be repeated
+    #[clap(long = "rewrite", value_name = "FIELD=VALUE", requires = "raw")]
+    pub rewrites: Vec<FrameRewrite>,
+    /// Recalculate the IP and UDP checksums of the re-emitted frames, which rewriting an address always does
+    #[clap(long = "recalculate-checksums", requires = "raw")]
+    pub recalculate_checksums: bool,
 }
 
 impl PlayerOptions {
@@ -45,9 +126,27 @@ impl PlayerOptions {
         Self {
             file,
             destination: SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), defaults::DEFAULT_DEST_PORT),
+            destination_mode: DestinationMode::Fixed,
+            mappings: Vec::new(),
+            filter: None,
+            bpf: None,
+            bind_address: None,
             source_port: DEFAULT_SRC_PORT,
             ttl: DEFAULT_TTL,
-            auto_play_disable: false
+            multicast_interface: None,
+            multicast_loopback: true,
+            auto_play_disable: false,
+            speed: DEFAULT_SPEED,
+            unthrottled: false,
+            loops: None,
+            section: None,
+            loop_gap: Duration::ZERO,
+            send_failure: SendFailurePolicy::Skip,
+            output: None,
+            output_start: OutputStart::Now,
+            raw: None,
+            rewrites: Vec::new(),
+            recalculate_checksums: false,
         }
     }
 
@@ -56,6 +155,31 @@ impl PlayerOptions {
         self
     }
 
+    pub fn with_destination_mode(mut self, destination_mode: DestinationMode) -> Self {
+        self.destination_mode = destination_mode;
+        self
+    }
+
+    pub fn with_mapping(mut self, mapping: DestinationMapping) -> Self {
+        self.mappings.push(mapping);
+        self
+    }
+
+    pub fn with_filter(mut self, filter: PacketFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn with_bpf(mut self, bpf: BpfFilter) -> Self {
+        self.bpf = Some(bpf);
+        self
+    }
+
+    pub fn with_bind_address(mut self, bind_address: BindAddress) -> Self {
+        self.bind_address = Some(bind_address);
+        self
+    }
+
     pub fn with_source_port(mut self, source_port: u16) -> Self {
         self.source_port = source_port;
         self
@@ -66,10 +190,118 @@ impl PlayerOptions {
         self
     }
 
+    pub fn with_multicast_interface(mut self, interface: MulticastInterface) -> Self {
+        self.multicast_interface = Some(interface);
+        self
+    }
+
+    pub fn with_multicast_loopback(mut self, loopback: bool) -> Self {
+        self.multicast_loopback = loopback;
+        self
+    }
+
     pub fn disable_auto_play(mut self) -> Self {
         self.auto_play_disable = true;
         self
     }
+
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn unthrottled(mut self) -> Self {
+        self.unthrottled = true;
+        self
+    }
+
+    /// Loop `times` times, or forever for 0.
+    pub fn with_loops(mut self, times: u32) -> Self {
+        self.loops = Some(times);
+        self
+    }
+
+    pub fn with_section(mut self, section: RepeatSection) -> Self {
+        self.section = Some(section);
+        self
+    }
+
+    pub fn with_loop_gap(mut self, gap: Duration) -> Self {
+        self.loop_gap = gap;
+        self
+    }
+
+    pub fn with_send_failure(mut self, send_failure: SendFailurePolicy) -> Self {
+        self.send_failure = send_failure;
+        self
+    }
+
+    pub fn with_output(mut self, output: String, start: OutputStart) -> Self {
+        self.output = Some(output);
+        self.output_start = start;
+        self
+    }
+
+    /// Re-emits the captured frames on the interface.
+    pub fn with_raw(mut self, interface: String) -> Self {
+        self.raw = Some(interface);
+        self
+    }
+
+    pub fn with_rewrite(mut self, rewrite: FrameRewrite) -> Self {
+        self.rewrites.push(rewrite);
+        self
+    }
+
+    pub fn with_recalculated_checksums(mut self) -> Self {
+        self.recalculate_checksums = true;
+        self
+    }
+
+    /// The repetition selected by the options.
+    pub fn repeat(&self) -> Repeat {
+        let repetitions = match (self.loops, self.section) {
+            (Some(0), _) | (None, Some(_)) => { Repetitions::Forever }
+            (Some(times), _) => { Repetitions::Times(times) }
+            (None, None) => { Repetitions::Once }
+        };
+        Repeat {
+            repetitions,
+            section: self.section,
+            gap: self.loop_gap,
+        }
+    }
+
+    /// The multicast options selected by the options.
+    pub fn multicast(&self) -> MulticastOptions {
+        MulticastOptions {
+            ttl: self.ttl,
+            interface: self.multicast_interface.clone(),
+            loopback: self.multicast_loopback,
+        }
+    }
+
+    /// The playback speed selected by the options.
+    pub fn playback_speed(&self) -> PlaybackSpeed {
+        if self.unthrottled {
+            PlaybackSpeed::Unthrottled
+        } else {
+            PlaybackSpeed::Multiplier(self.speed)
+        }
+    }
+}
+
+fn parse_gap(value: &str) -> Result<Duration, String> {
+    value.parse::<f64>().ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("`{value}` is not a number of seconds"))
+}
+
+fn parse_speed(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(speed) if (MIN_SPEED..=MAX_SPEED).contains(&speed) => { Ok(speed) }
+        _ => { Err(format!("`{value}` is not a number from {MIN_SPEED} to {MAX_SPEED}")) }
+    }
 }
 
 #[derive(Clone, Debug, Error, Serialize)]
@@ -78,6 +310,20 @@ pub enum PlayerError {
     PlayerInitError,
     #[error("The command channel failed")]
     CommandChannelError,
+    #[error("Failed to bind the socket to port {port}: {reason}")]
+    BindError { port: u16, reason: String },
+    #[error("Failed to set the socket option {option}: {reason}")]
+    SocketOptionError { option: &'static str, reason: String },
+    #[error("Failed to send packet {packet}: {reason}")]
+    SendError { packet: usize, reason: String },
+    #[error("No packets to play, the filters exclude all of them")]
+    NoPackets,
+    #[error("Cannot seek to packet {position}, there are only {packets} packets")]
+    SeekPositionOutOfRange { position: usize, packets: usize },
+    #[error("Cannot seek to {time:?}, the recording lasts only {duration:?}")]
+    SeekTimeOutOfRange { time: Duration, duration: Duration },
+    #[error("Cannot step through the packets while playing, pause first")]
+    StepWhilePlaying,
 }
 
 #[derive(Clone, Debug, Error)]
@@ -88,45 +334,10 @@ pub enum FileError {
     NotAFile,
     #[error("The provided path does not exist")]
     PathDoesNotExist,
-    #[error("Failed to parse the file")]
-    ParseError,
+    #[error("Could not read the file: {0}")]
+    ReadError(String),
+    #[error("Failed to parse the file: {0}")]
+    ParseError(PcapError),
+    #[error("The file contains no packets")]
+    NoPackets,
 }
-
-// TODO add other supported types here
-const SUPPORTED_EXTENSIONS: [&str; 1] = ["pcap"];
-#[derive(Debug)]
-pub enum Recording {
-    Pcap(Pcap),
-    PcapNg(PcapNG),
-}
-
-impl TryFrom<&str> for Recording {
-    type Error = FileError;
-
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let file_path = std::path::Path::new(value);
-        if !file_path.exists() {
-            return Err(FileError::PathDoesNotExist);
-        };
-        if !file_path.is_file() {
-            return Err(FileError::NotAFile);
-        }
-
-        match file_path.extension().and_then(OsStr::to_str) {
-            None => { return Err(FileError::FileTypeNotSupported(String::from(""))); }
-            Some(os_str) => {
-                if !SUPPORTED_EXTENSIONS.contains(&os_str) {
-                    return Err(FileError::FileTypeNotSupported(os_str.to_string()));
-                }
-            }
-        }
-
-        let file = File::open(file_path).expect("Could not open file.");
-
-        return if let Ok(recording) = Pcap::try_from(file) {
-            Ok(Recording::Pcap(recording))
-        } else {
-            Err(FileError::ParseError)
-        }
-    }
-}
\ No newline at end of file
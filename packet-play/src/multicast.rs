@@ -0,0 +1,194 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::str::FromStr;
+
+use serde_derive::Serialize;
+use socket2::SockRef;
+
+use crate::defaults::DEFAULT_TTL;
+use crate::PlayerError;
+
+/// How multicast datagrams leave the host: their TTL or hop limit, the interface they are sent from
+/// and whether they are looped back to the sending host.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MulticastOptions {
+    pub ttl: u32,                                   // the IPv4 TTL or the IPv6 hop limit
+    pub interface: Option<MulticastInterface>,      // the system default when not given
+    pub loopback: bool,
+}
+
+/// The interface to send multicast from, written as an address of the interface or its name, e.g. `10.0.0.5` or `eth1`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum MulticastInterface {
+    Address(IpAddr),
+    Name(String),
+}
+
+impl Default for MulticastOptions {
+    fn default() -> Self {
+        Self {
+            ttl: DEFAULT_TTL,
+            interface: None,
+            loopback: true,
+        }
+    }
+}
+
+impl MulticastOptions {
+    pub fn new(ttl: u32) -> Self {
+        Self { ttl, ..Self::default() }
+    }
+
+    pub fn with_interface(self, interface: MulticastInterface) -> Self {
+        Self { interface: Some(interface), ..self }
+    }
+
+    pub fn with_loopback(self, loopback: bool) -> Self {
+        Self { loopback, ..self }
+    }
+
+    /// Sets the multicast options on the socket, for the address family it is bound to.
+    pub fn apply(&self, socket: &UdpSocket) -> Result<(), PlayerError> {
+        let ipv6 = socket.local_addr()
+            .map_err(|err| PlayerError::SocketOptionError { option: "SO_DOMAIN", reason: err.to_string() })?
+            .is_ipv6();
+        let socket = SockRef::from(socket);
+        let failed = |option: &'static str| move |err: std::io::Error| PlayerError::SocketOptionError { option, reason: err.to_string() };
+
+        if ipv6 {
+            socket.set_multicast_hops_v6(self.ttl).map_err(failed("IPV6_MULTICAST_HOPS"))?;
+            socket.set_multicast_loop_v6(self.loopback).map_err(failed("IPV6_MULTICAST_LOOP"))?;
+            if let Some(interface) = &self.interface {
+                let index = interface.index().map_err(failed("IPV6_MULTICAST_IF"))?;
+                socket.set_multicast_if_v6(index).map_err(failed("IPV6_MULTICAST_IF"))?;
+            }
+        } else {
+            socket.set_multicast_ttl_v4(self.ttl).map_err(failed("IP_MULTICAST_TTL"))?;
+            socket.set_multicast_loop_v4(self.loopback).map_err(failed("IP_MULTICAST_LOOP"))?;
+            if let Some(interface) = &self.interface {
+                let address = interface.ipv4_address().map_err(failed("IP_MULTICAST_IF"))?;
+                socket.set_multicast_if_v4(&address).map_err(failed("IP_MULTICAST_IF"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for MulticastOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ttl {}", self.ttl)?;
+        if let Some(interface) = &self.interface {
+            write!(f, " via {interface}")?;
+        }
+        write!(f, ", loopback {}", if self.loopback { "on" } else { "off" })
+    }
+}
+
+impl MulticastInterface {
+    /// The IPv4 address of the interface, for `IP_MULTICAST_IF`.
+    fn ipv4_address(&self) -> Result<Ipv4Addr, std::io::Error> {
+        if let MulticastInterface::Address(IpAddr::V4(address)) = self {
+            return Ok(*address);
+        }
+        self.interfaces()?.iter()
+            .find_map(|interface| match interface.ip() {
+                IpAddr::V4(address) => { Some(address) }
+                IpAddr::V6(_) => { None }
+            })
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("Interface {self} has no IPv4 address")))
+    }
+
+    /// The index of the interface, for `IPV6_MULTICAST_IF`.
+    pub(crate) fn index(&self) -> Result<u32, std::io::Error> {
+        #[cfg(target_os = "linux")]
+        if let MulticastInterface::Name(name) = self {
+            return interface_index(name);
+        }
+        self.interfaces()?.iter()
+            .find_map(|interface| interface.index)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("Interface {self} has no index")))
+    }
+
+    /// The addresses of the interface, found by name or by one of its addresses.
+    fn interfaces(&self) -> Result<Vec<if_addrs::Interface>, std::io::Error> {
+        let all = if_addrs::get_if_addrs()?;
+        let name = match self {
+            MulticastInterface::Name(name) => { Some(name.as_str()) }
+            MulticastInterface::Address(address) => {
+                all.iter().find(|interface| interface.ip() == *address).map(|interface| interface.name.as_str())
+            }
+        };
+        let found: Vec<_> = all.iter().filter(|interface| Some(interface.name.as_str()) == name).cloned().collect();
+        if found.is_empty() {
+            Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("No interface {self}")))
+        } else {
+            Ok(found)
+        }
+    }
+}
+
+/// The index of the interface by its name, also of interfaces that `getifaddrs` lists no addresses of.
+#[cfg(target_os = "linux")]
+pub(crate) fn interface_index(name: &str) -> Result<u32, std::io::Error> {
+    let c_name = std::ffi::CString::new(name)
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid interface name `{name}`")))?;
+    let index = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+    if index == 0 {
+        Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("No interface {name}")))
+    } else {
+        Ok(index)
+    }
+}
+
+impl FromStr for MulticastInterface {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            Err("Expected an interface address or name".to_string())
+        } else if let Ok(address) = s.parse::<IpAddr>() {
+            Ok(MulticastInterface::Address(address))
+        } else {
+            Ok(MulticastInterface::Name(s.to_string()))
+        }
+    }
+}
+
+impl Display for MulticastInterface {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MulticastInterface::Address(address) => { write!(f, "{address}") }
+            MulticastInterface::Name(name) => { write!(f, "{name}") }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv6Addr, SocketAddr};
+
+    use super::*;
+
+    #[test]
+    fn parse_interface() {
+        assert_eq!("10.0.0.5".parse(), Ok(MulticastInterface::Address(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5)))));
+        assert_eq!("::1".parse(), Ok(MulticastInterface::Address(IpAddr::V6(Ipv6Addr::LOCALHOST))));
+        assert_eq!("eth1".parse(), Ok(MulticastInterface::Name("eth1".to_string())));
+        assert!("".parse::<MulticastInterface>().is_err());
+    }
+
+    #[test]
+    fn apply_to_socket() {
+        let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)).unwrap();
+        let options = MulticastOptions::new(4)
+            .with_interface(MulticastInterface::Address(IpAddr::V4(Ipv4Addr::LOCALHOST)))
+            .with_loopback(false);
+        options.apply(&socket).unwrap();
+        assert_eq!(socket.multicast_ttl_v4().unwrap(), 4);
+        assert!(!socket.multicast_loop_v4().unwrap());
+
+        let missing = MulticastOptions::default().with_interface(MulticastInterface::Name("no-such-if0".to_string()));
+        assert!(matches!(missing.apply(&socket), Err(PlayerError::SocketOptionError { option: "IP_MULTICAST_IF", .. })));
+    }
+}
@@ -0,0 +1,103 @@
+use std::fmt::{Display, Formatter};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_derive::Serialize;
+
+use crate::constants::SPIN_THRESHOLD;
+use crate::player::PlaybackSpeed;
+
+/// Schedules packets against an absolute reference: the instant the schedule was anchored plus the
+/// recorded offset since then, so that timing errors do not accumulate over a long replay.
+#[derive(Debug, Default)]
+pub(crate) struct Pacing {
+    reference: Option<(Instant, Duration)>,     // the instant at which the recorded offset was anchored
+    last_lateness: Option<Duration>,
+    report: PacingReport,
+}
+
+/// How accurately the `Player` keeps to the recorded timing.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize)]
+pub struct PacingReport {
+    pub drift: Duration,        // how late the last packet was sent
+    pub jitter: Duration,       // the smoothed variation in lateness between packets
+    pub max_drift: Duration,    // how late the latest packet so far was sent
+}
+
+impl Pacing {
+    /// Anchors the schedule anew at the next packet, after a pause, seek or speed change.
+    pub(crate) fn reset(&mut self) {
+        self.reference = None;
+        self.last_lateness = None;
+    }
+
+    /// The instant the packet recorded at `timestamp` is due, anchoring the schedule
+    /// at the `previous` recorded offset when needed.
+    pub(crate) fn due(&mut self, timestamp: Duration, previous: Duration, speed: PlaybackSpeed) -> Instant {
+        let (start, offset) = *self.reference.get_or_insert_with(|| (Instant::now(), previous));
+        start + speed.scale(timestamp.saturating_sub(offset))
+    }
+
+    /// Records how late a packet was sent.
+    pub(crate) fn record(&mut self, lateness: Duration) {
+        if let Some(last) = self.last_lateness {
+            // Smoothed like the interarrival jitter of RFC 3550.
+            let jitter = self.report.jitter.as_secs_f64();
+            let variation = lateness.abs_diff(last).as_secs_f64();
+            self.report.jitter = Duration::from_secs_f64(jitter + (variation - jitter) / 16.0);
+        }
+        self.last_lateness = Some(lateness);
+        self.report.drift = lateness;
+        self.report.max_drift = self.report.max_drift.max(lateness);
+    }
+
+    pub(crate) fn report(&self) -> PacingReport {
+        self.report
+    }
+}
+
+/// Waits until the deadline, sleeping while it is far off and spinning for the last stretch,
+/// which sleeping would overshoot.
+pub(crate) fn wait_until(deadline: Instant) {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        } else if remaining > SPIN_THRESHOLD {
+            thread::sleep(remaining - SPIN_THRESHOLD);
+        } else {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+impl Display for PacingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "drift {:.2?} (max {:.2?}), jitter {:.2?}", self.drift, self.max_drift, self.jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_against_the_anchor() {
+        let mut pacing = Pacing::default();
+        let first = pacing.due(Duration::from_secs(10), Duration::from_secs(10), PlaybackSpeed::Multiplier(2.0));
+        let later = pacing.due(Duration::from_secs(14), Duration::from_secs(12), PlaybackSpeed::Multiplier(2.0));
+        assert_eq!(later - first, Duration::from_secs(2));
+
+        pacing.record(Duration::from_micros(100));
+        pacing.record(Duration::from_micros(260));
+        let report = pacing.report();
+        assert_eq!(report.drift, Duration::from_micros(260));
+        assert_eq!(report.max_drift, Duration::from_micros(260));
+        assert_eq!(report.jitter.as_micros(), 10);
+
+        pacing.reset();
+        let anchored = pacing.due(Duration::from_secs(20), Duration::from_secs(20), PlaybackSpeed::default());
+        assert!(anchored >= first);
+        assert_eq!(pacing.report().max_drift, Duration::from_micros(260));
+    }
+}
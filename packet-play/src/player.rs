@@ -1,29 +1,47 @@
 use std::fmt::{Display, Formatter};
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::iter::Enumerate;
+use std::str::FromStr;
+use std::net::SocketAddr;
+use std::slice::Iter;
 use std::sync::mpsc::{Receiver, Sender, TryRecvError};
 use std::thread;
 use std::thread::JoinHandle;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use log::trace;
+use clap::ValueEnum;
+use log::{trace, warn};
 
 use serde_derive::Serialize;
 
-use pcap_files::{PcapMagicNumber, PcapPacketRecord};
-use pcap_files::{ETHERNET_HEADER_LENGTH_BYTES, IP_HEADER_LENGTH_BYTES, UDP_HEADER_LENGTH_BYTES};
+use packet_rehash_core::sink::{CapturedFrame, PacketSink, SinkPacket};
+use pcap_files::BpfFilter;
 
-use crate::{PlayerError, Recording};
+use crate::{DestinationMapping, DestinationMode, PacketFilter, PlayerError, Recording, DEFAULT_SRC_PORT, DEFAULT_TTL, MAX_SPEED, MIN_SPEED, SPEED_STEPS};
+use crate::constants::{COMMAND_POLL_INTERVAL, PACING_REPORT_INTERVAL, SEND_RETRIES, SEND_RETRY_DELAY, UNTHROTTLED_POSITION_INTERVAL};
+use crate::pacing::{self, Pacing};
+use crate::repeat::Repeat;
 use crate::commands::Command;
+use crate::destination::Destinations;
 use crate::events::Event;
-
-const STRIP_HEADERS_INDEX: usize =
-    (ETHERNET_HEADER_LENGTH_BYTES + IP_HEADER_LENGTH_BYTES + UDP_HEADER_LENGTH_BYTES + 1) as usize;
+use crate::recording::Datagram;
+use crate::bind::BindAddress;
+use crate::multicast::MulticastOptions;
+use crate::sinks::UdpSink;
 
 pub struct Player {
     recording: Recording,
-    destination: SocketAddr,
+    destinations: Destinations,
+    filter: PacketFilter,
+    bpf: Option<BpfFilter>,
+    bind_address: Option<BindAddress>,   // both unspecified addresses when `None`
     source_port: u16,
     ttl: u32,
+    multicast: MulticastOptions,
+    speed: PlaybackSpeed,
+    repeat: Repeat,
+    send_failure: SendFailurePolicy,
+    sink: Option<Box<dyn PacketSink>>,     // a `UdpSink` from the source port when `None`
+    simulated_start: Option<OutputStart>,   // replay in simulated time, from this start
     state: PlayerState,
     cmd_rx: Receiver<Command>,
     event_tx: Sender<Event>,
@@ -50,94 +68,340 @@ impl Display for PlayerState {
     }
 }
 
+/// The rate at which the `Player` replays the recording.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub enum PlaybackSpeed {
+    Multiplier(f64),    // relative to the timing of the recording, e.g. 2.0 plays twice as fast
+    Unthrottled,        // without delays between packets
+}
+
+impl PlaybackSpeed {
+    /// The real time to wait for a gap of `duration` in the recording.
+    pub fn scale(&self, duration: Duration) -> Duration {
+        match self {
+            PlaybackSpeed::Multiplier(multiplier) => {
+                Duration::try_from_secs_f64(duration.as_secs_f64() / multiplier).unwrap_or(Duration::MAX)
+            }
+            PlaybackSpeed::Unthrottled => { Duration::ZERO }
+        }
+    }
+
+    /// The speed with its multiplier clamped to `MIN_SPEED..=MAX_SPEED`.
+    pub fn clamped(&self) -> Self {
+        match self {
+            PlaybackSpeed::Multiplier(multiplier) if multiplier.is_nan() => { PlaybackSpeed::default() }
+            PlaybackSpeed::Multiplier(multiplier) => { PlaybackSpeed::Multiplier(multiplier.clamp(MIN_SPEED, MAX_SPEED)) }
+            PlaybackSpeed::Unthrottled => { PlaybackSpeed::Unthrottled }
+        }
+    }
+
+    /// The next faster of the `SPEED_STEPS`. Unthrottled counts as faster than all steps,
+    /// so frontends return to the last multiplier themselves.
+    pub fn faster(&self) -> f64 {
+        let multiplier = self.multiplier();
+        SPEED_STEPS.iter().copied().find(|step| *step > multiplier).unwrap_or(multiplier)
+    }
+
+    /// The next slower of the `SPEED_STEPS`.
+    pub fn slower(&self) -> f64 {
+        let multiplier = self.multiplier();
+        SPEED_STEPS.iter().rev().copied().find(|step| *step < multiplier).unwrap_or(multiplier)
+    }
+
+    fn multiplier(&self) -> f64 {
+        match self {
+            PlaybackSpeed::Multiplier(multiplier) => { *multiplier }
+            PlaybackSpeed::Unthrottled => { f64::INFINITY }
+        }
+    }
+}
+
+/// What the `Player` does when sending a packet fails, besides reporting the error.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum, Serialize)]
+pub enum SendFailurePolicy {
+    #[default]
+    Skip,       // continue with the next packet
+    Retry,      // retry a few times before skipping the packet
+    Pause,      // pause the playback
+}
+
+impl Display for SendFailurePolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendFailurePolicy::Skip => { write!(f, "skip") }
+            SendFailurePolicy::Retry => { write!(f, "retry") }
+            SendFailurePolicy::Pause => { write!(f, "pause") }
+        }
+    }
+}
+
+/// When the timestamps of simulated playback start.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize)]
+pub enum OutputStart {
+    #[default]
+    Now,
+    Original,       // at the captured timestamp of the first packet
+    At(Duration),   // since the epoch
+}
+
+impl FromStr for OutputStart {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "now" => { Ok(OutputStart::Now) }
+            "original" => { Ok(OutputStart::Original) }
+            seconds => {
+                seconds.parse::<f64>().ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .map(OutputStart::At)
+                    .ok_or_else(|| format!("Expected `now`, `original` or seconds since the epoch, found `{s}`"))
+            }
+        }
+    }
+}
+
+impl Display for OutputStart {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputStart::Now => { write!(f, "now") }
+            OutputStart::Original => { write!(f, "original") }
+            OutputStart::At(time) => { write!(f, "{}", time.as_secs_f64()) }
+        }
+    }
+}
+
+impl Default for PlaybackSpeed {
+    fn default() -> Self {
+        PlaybackSpeed::Multiplier(1.0)
+    }
+}
+
+impl Display for PlaybackSpeed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlaybackSpeed::Multiplier(multiplier) => { write!(f, "{multiplier}x") }
+            PlaybackSpeed::Unthrottled => { write!(f, "unthrottled") }
+        }
+    }
+}
+
+/// The current time, since the epoch.
+fn since_epoch() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+}
+
+/// Iterates the datagrams from the given index onwards, skipping ahead in constant time.
+fn packets_from<'a>(datagrams: &'a [Datagram<'a>], index: usize) -> Enumerate<Iter<'a, Datagram<'a>>> {
+    let mut packets = datagrams.iter().enumerate();
+    if index > 0 {
+        packets.nth(index - 1);
+    }
+    packets
+}
+
 impl Player {
-    pub fn run(&mut self) {
-        let socket = UdpSocket::bind(
-            SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), self.source_port))
-            .expect(format!("Failed to bind socket to port {:?}", self.source_port).as_str());
-        socket.set_broadcast(true).expect("Failed to set socket SO_BROADCAST option.");
-        socket.set_ttl(self.ttl).expect("Failed to set socket TTL value");
-
-        // TODO refactor 'return' to Result<> function return value
-        let recording = match &self.recording {
-            Recording::Pcap(pcap) => pcap,
-            Recording::PcapNg(_) => {
-                let _ = self.event_tx.send(
-                    Event::error(
-                        PlayerError::PlayerInitError));
-                return;
-            },
+    /// Plays the recording until quit. Errors that stop the `Player` are also sent as `Event::Error`.
+    pub fn run(&mut self) -> Result<(), PlayerError> {
+        let mut sink = match self.sink.take() {
+            Some(sink) => { sink }
+            None => {
+                let sink = match &self.bind_address {
+                    Some(address) => { UdpSink::bind(address, self.source_port, self.ttl, &self.multicast) }
+                    None => { UdpSink::new(self.source_port, self.ttl, &self.multicast) }
+                };
+                Box::new(sink.map_err(|error| self.report(error))?)
+            }
         };
-        trace!("{:?}", recording.header);
 
-        let first_ts = duration_from_timestamp(&recording.header.magic_number, &recording.packets.first().unwrap());
-        let last_ts = duration_from_timestamp(&recording.header.magic_number, &recording.packets.last().unwrap());
-        let total_duration = last_ts - first_ts;
+        let datagrams = self.recording.datagrams(&self.filter, self.bpf.as_ref());
+        trace!("Loaded {} datagrams for playback", datagrams.len());
+        if datagrams.is_empty() {
+            return Err(self.report(PlayerError::NoPackets));
+        }
+
+        let first_ts = datagrams.first().map(|datagram| datagram.timestamp).unwrap_or_default();
+        let last_ts = datagrams.iter().map(|datagram| datagram.timestamp).max().unwrap_or_default();
+        let total_duration = last_ts.saturating_sub(first_ts);
+        // Captures merged from several interfaces need not be in order of time, and seeking by time then searches linearly.
+        let sorted = datagrams.is_sorted_by_key(|datagram| datagram.timestamp);
+
+        let (repeat_start, repeat_end) = self.repeat.section
+            .and_then(|section| {
+                let indices = section.resolve(&datagrams, sorted);
+                if indices.is_none() {
+                    warn!("Repeating the whole recording, the section {section} contains no packets");
+                }
+                indices
+            })
+            .unwrap_or((0, datagrams.len().saturating_sub(1)));
 
-        let mut packets = recording.packets.iter().enumerate();
-        let mut previous_ts = first_ts.clone();
-        let mut playback_elapsed = previous_ts - first_ts;
+        // An A-B section plays from A, also in the first iteration.
+        let mut packets = packets_from(&datagrams, repeat_start);
+        let mut previous_ts = datagrams[repeat_start].timestamp;
+        let mut playback_elapsed = previous_ts.saturating_sub(first_ts);
+        let mut iteration = 1;
 
         let _ = self.event_tx.send(Event::PlayerReady);
         let _ = self.event_tx.send(Event::state_event(PlayerState::Initial));
-        let _ = self.event_tx.send(Event::position_event(0,recording.packets.len(),playback_elapsed, total_duration));
-
-        let mut loop_time_start : Option<Instant> = None;
+        let _ = self.event_tx.send(Event::position_event(repeat_start, datagrams.len(), playback_elapsed, total_duration, self.speed));
+        let _ = self.event_tx.send(Event::iteration_event(iteration, self.repeat.repetitions));
+
+        let mut pacing = Pacing::default();
+        let mut last_pacing_event = Instant::now();
+        let mut last_position_event = Instant::now();
+        let mut position = repeat_start;
+        let mut resume_at : Option<Instant> = None;
+
+        // Simulated playback stamps the packets with the time they would have been sent, without waiting.
+        let simulated_start = self.simulated_start.map(|start| match start {
+            OutputStart::Now => { since_epoch() }
+            OutputStart::Original => { first_ts }
+            OutputStart::At(time) => { time }
+        });
+        let mut simulated_elapsed = Duration::ZERO;
 
         loop {
-            // receive any command and update state
-            if let Some(new_state) = match self.cmd_rx.try_recv() {
+            // receive any command and update state, seeking by time to the first packet at or after that time
+            let received = self.cmd_rx.try_recv().map(|command| match command {
+                Command::SeekTime(time) if time <= total_duration => {
+                    let before = |datagram: &Datagram| datagram.timestamp.saturating_sub(first_ts) < time;
+                    Command::Seek(if sorted {
+                        datagrams.partition_point(before)
+                    } else {
+                        datagrams.iter().position(|datagram| !before(datagram)).unwrap_or(datagrams.len())
+                    })
+                }
+                command => { command }
+            });
+            if let Some(new_state) = match received {
                 Ok(Command::Play) => {
-                    if self.state == PlayerState::Initial {}
                     Some(PlayerState::Playing)
                 }
                 Ok(Command::Pause) => {
                     Some(PlayerState::Paused)
                 }
                 Ok(Command::Rewind) => {
-                    packets = recording.packets.iter().enumerate();
-                    previous_ts = duration_from_timestamp(&recording.header.magic_number, &recording.packets.first().unwrap());
-                    playback_elapsed = Duration::new(0,0);
+                    packets = packets_from(&datagrams, repeat_start);
+                    previous_ts = datagrams[repeat_start].timestamp;
+                    playback_elapsed = previous_ts.saturating_sub(first_ts);
+                    position = repeat_start;
+                    iteration = 1;
+                    resume_at = None;
                     let _ = self.event_tx.send(
                         Event::position_event(
-                            0 ,packets.len(),
-                            playback_elapsed.clone(), total_duration.clone()));
+                            repeat_start, datagrams.len(),
+                            playback_elapsed, total_duration, self.speed));
+                    let _ = self.event_tx.send(Event::iteration_event(iteration, self.repeat.repetitions));
                     Some(PlayerState::Initial)
                 }
                 Ok(Command::Seek(to_position)) => {
-                    if to_position < recording.packets.len() {
-                        packets = recording.packets.iter().enumerate();
-                        (0..to_position).for_each(|_| {packets.next();} );
-                        // for _ in 0..to_position {
-                        //     // FIXME manually looping to the sought packet is not very nice
-                        //     let _ = packets.next();
-                        // }
-                        if let Some((pos, sought_packet)) = packets.next() {
-                            previous_ts = duration_from_timestamp(&recording.header.magic_number, &sought_packet);
-                            playback_elapsed = previous_ts.saturating_sub(first_ts);
-                            let _ = self.event_tx.send(
-                                Event::position_event(
-                                    pos ,recording.packets.len(),
-                                    playback_elapsed.clone(), total_duration.clone()));
-
-                            if self.state == PlayerState::Playing {
-                                Some(PlayerState::Playing)
-                            } else {
-                                Some(PlayerState::Paused)
-                            }
-                        } else { None }
+                    if let Some((pos, sought_packet)) = packets_from(&datagrams, to_position).next() {
+                        packets = packets_from(&datagrams, to_position);
+                        previous_ts = sought_packet.timestamp;
+                        playback_elapsed = previous_ts.saturating_sub(first_ts);
+                        position = pos;
+                        pacing.reset();
+                        let _ = self.event_tx.send(
+                            Event::position_event(
+                                pos ,datagrams.len(),
+                                playback_elapsed, total_duration, self.speed));
+
+                        if self.state == PlayerState::Playing {
+                            Some(PlayerState::Playing)
+                        } else {
+                            Some(PlayerState::Paused)
+                        }
                     } else {
-                        // TODO when an invalid/too large to_position is provided.
+                        let _ = self.event_tx.send(Event::error(
+                            PlayerError::SeekPositionOutOfRange { position: to_position, packets: datagrams.len() }));
                         None
                     }
                 }
+                Ok(Command::SeekTime(time)) => {
+                    let _ = self.event_tx.send(Event::error(
+                        PlayerError::SeekTimeOutOfRange { time, duration: total_duration }));
+                    None
+                }
+                Ok(Command::StepForward(0)) => { None }
+                Ok(command @ (Command::StepForward(_) | Command::StepBackward(_))) => {
+                    if self.state == PlayerState::Playing {
+                        let _ = self.event_tx.send(Event::error(PlayerError::StepWhilePlaying));
+                        None
+                    } else {
+                        // The index of the packet that is sent next, to step relative to the last sent packet.
+                        let next = packets.clone().next().map_or(datagrams.len(), |(i, _)| i);
+                        let sent_at = simulated_start.map_or_else(since_epoch, |start| start + simulated_elapsed);
+                        let sent = match command {
+                            Command::StepForward(steps) => {
+                                // Within an A-B section, stop at B.
+                                let steps = steps.min((repeat_end + 1).saturating_sub(next));
+                                packets.by_ref().take(steps).map(|(i, packet)| {
+                                    if let Err(error) = self.send(&mut sink, i, packet, sent_at) {
+                                        self.report(error);
+                                    }
+                                    (i, packet)
+                                }).last()
+                            }
+                            Command::StepBackward(steps) => {
+                                next.checked_sub(1).and_then(|last| last.checked_sub(steps)).map(|target| {
+                                    packets = packets_from(&datagrams, target);
+                                    let (i, packet) = packets.next().expect("target precedes the next packet");
+                                    if let Err(error) = self.send(&mut sink, i, packet, sent_at) {
+                                        self.report(error);
+                                    }
+                                    (i, packet)
+                                })
+                            }
+                            _ => { None }
+                        };
+                        if let Some((i, packet)) = sent {
+                            previous_ts = packet.timestamp;
+                            playback_elapsed = previous_ts.saturating_sub(first_ts);
+                            position = i;
+                            pacing.reset();
+                            let _ = self.event_tx.send(Event::position_event(
+                                position, datagrams.len(), playback_elapsed, total_duration, self.speed));
+                            Some(PlayerState::Paused)
+                        } else {
+                            // Stepping past either end of the recording or the section, where no packet is left to send.
+                            let (position, count) = match command {
+                                Command::StepForward(steps) => { (next.saturating_add(steps - 1), repeat_end + 1) }
+                                _ => { (0, datagrams.len()) }
+                            };
+                            let _ = self.event_tx.send(Event::error(
+                                PlayerError::SeekPositionOutOfRange { position, packets: count }));
+                            None
+                        }
+                    }
+                }
+                Ok(Command::SetSpeed(multiplier)) => {
+                    if multiplier > 0.0 {
+                        self.speed = PlaybackSpeed::Multiplier(multiplier).clamped();
+                        pacing.reset();
+                        let _ = self.event_tx.send(Event::position_event(
+                            position, datagrams.len(), playback_elapsed, total_duration, self.speed));
+                    } else {
+                        warn!("Ignoring invalid playback speed {multiplier}");
+                    }
+                    None
+                }
+                Ok(Command::SetUnthrottled) => {
+                    self.speed = PlaybackSpeed::Unthrottled;
+                    pacing.reset();
+                    let _ = self.event_tx.send(Event::position_event(
+                        position, datagrams.len(), playback_elapsed, total_duration, self.speed));
+                    None
+                }
                 Ok(Command::Quit) => { Some(PlayerState::Quit) }
                 Err(TryRecvError::Empty) => { None } // no-op
                 Err(TryRecvError::Disconnected) => {
-                    let _ = self.event_tx.send(Event::error(PlayerError::CommandChannelError)).unwrap();
-                    Some(PlayerState::Quit)
+                    return Err(self.report(PlayerError::CommandChannelError));
                 }
             } {
+                if new_state != PlayerState::Playing {
+                    pacing.reset();
+                }
                 let _ = self.event_tx.send(Event::state_event(new_state));
                 self.state = new_state;
             };
@@ -146,79 +410,177 @@ impl Player {
             match self.state {
                 PlayerState::Initial => {} // no-op
                 PlayerState::Playing => {
-                    if let Some((i, packet)) = packets.next() {
-                        let current_ts = duration_from_timestamp(&recording.header.magic_number, &packet);
-                        let ts_duration = current_ts.saturating_sub(previous_ts);
-
-                        let loop_duration = if let Some(start) = loop_time_start {
-                            start.elapsed()
-                        } else { Duration::new(0, 0) };
-
-                        thread::sleep(ts_duration.saturating_sub(loop_duration));
+                    if let Some(resume) = resume_at {
+                        // Pause between iterations, while still handling commands.
+                        let remaining = resume.saturating_duration_since(Instant::now());
+                        if !remaining.is_zero() {
+                            thread::sleep(remaining.min(COMMAND_POLL_INTERVAL));
+                            continue;
+                        }
+                        resume_at = None;
+                    }
 
-                        loop_time_start = Some(Instant::now());
+                    let iteration_ended = if let Some((i, packet)) = packets.clone().next() {
+                        let current_ts = packet.timestamp;
+                        if simulated_start.is_some() {
+                            // Only the clock of the simulation waits.
+                            simulated_elapsed += self.speed.scale(current_ts.saturating_sub(previous_ts));
+                            packets.next();
+                        } else {
+                            let due = pacing.due(current_ts, previous_ts, self.speed);
+                            if due.saturating_duration_since(Instant::now()) > COMMAND_POLL_INTERVAL {
+                                // Keep handling commands while waiting for a distant packet.
+                                thread::sleep(COMMAND_POLL_INTERVAL);
+                                continue;
+                            }
+                            pacing::wait_until(due);
+                            packets.next();
+
+                            if self.speed != PlaybackSpeed::Unthrottled {
+                                pacing.record(due.elapsed());
+                                if last_pacing_event.elapsed() >= PACING_REPORT_INTERVAL {
+                                    last_pacing_event = Instant::now();
+                                    let _ = self.event_tx.send(Event::PlayerPacingChanged(pacing.report()));
+                                }
+                            }
+                        }
 
                         previous_ts = current_ts;
-                        playback_elapsed = current_ts - first_ts;
-
-                        let _ = self.event_tx.send(Event::position_event(
-                            i,
-                            recording.packets.len(),
-                            playback_elapsed.clone(),
-                            total_duration.clone()
-                        ));
-
-                        let _bytes_send = socket.send_to(
-                            &packet.packet_data.as_slice()[STRIP_HEADERS_INDEX..],
-                            self.destination)
-                            .expect("Could not send packet");
-                    } else {
-                        let _ = self.event_tx.send(Event::state_event(PlayerState::Finished));
-                        self.state = PlayerState::Finished;
+                        playback_elapsed = current_ts.saturating_sub(first_ts);
+                        position = i;
+
+                        // Unthrottled or simulated playback would flood the frontends with an event per packet.
+                        if (self.speed != PlaybackSpeed::Unthrottled && simulated_start.is_none())
+                            || last_position_event.elapsed() >= UNTHROTTLED_POSITION_INTERVAL
+                            || i == repeat_end {
+                            last_position_event = Instant::now();
+                            let _ = self.event_tx.send(Event::position_event(
+                                i,
+                                datagrams.len(),
+                                playback_elapsed,
+                                total_duration,
+                                self.speed
+                            ));
+                        }
+
+                        let sent_at = simulated_start.map_or_else(since_epoch, |start| start + simulated_elapsed);
+                        if let Err(error) = self.send(&mut sink, i, packet, sent_at) {
+                            self.report(error);
+                            if self.send_failure == SendFailurePolicy::Pause {
+                                let _ = self.event_tx.send(Event::state_event(PlayerState::Paused));
+                                self.state = PlayerState::Paused;
+                                pacing.reset();
+                            }
+                        }
+                        i == repeat_end
+                    } else { true };
+
+                    if iteration_ended {
+                        if !datagrams.is_empty() && self.repeat.repetitions.includes(iteration + 1) {
+                            iteration += 1;
+                            packets = packets_from(&datagrams, repeat_start);
+                            previous_ts = datagrams[repeat_start].timestamp;
+                            pacing.reset();
+                            if simulated_start.is_some() {
+                                simulated_elapsed += self.repeat.gap;
+                            } else {
+                                resume_at = Some(Instant::now() + self.repeat.gap);
+                            }
+                            let _ = self.event_tx.send(Event::iteration_event(iteration, self.repeat.repetitions));
+                        } else {
+                            if let Err(err) = sink.flush() {
+                                self.report(PlayerError::SendError { packet: position + 1, reason: err.to_string() });
+                            }
+                            let _ = self.event_tx.send(Event::state_event(PlayerState::Finished));
+                            self.state = PlayerState::Finished;
+                        }
                     }
                 }
                 PlayerState::Paused => { } // no-op
                 PlayerState::Finished => { } // no-op
                 PlayerState::Quit => {
+                    if let Err(err) = sink.flush() {
+                        self.report(PlayerError::SendError { packet: position + 1, reason: err.to_string() });
+                    }
                     let _ = self.event_tx.send(Event::QuitCommanded);
                     break;
                 }
             }
         }
+        Ok(())
+    }
+
+    /// Sends a packet, retrying a failed send when the policy says so.
+    fn send(&self, sink: &mut dyn PacketSink, index: usize, packet: &Datagram, sent_at: Duration) -> Result<(), PlayerError> {
+        let packet = SinkPacket {
+            timestamp: packet.timestamp,
+            sent_at,
+            source: packet.source,
+            destination: self.destinations.resolve(packet.destination),
+            payload: &packet.payload,
+            captured: packet.frame.map(|frame| CapturedFrame { link_type: frame.link_type, data: frame.data }),
+        };
+        let mut retries = 0;
+        loop {
+            match sink.send(&packet) {
+                Ok(()) => { return Ok(()) }
+                Err(err) if self.send_failure == SendFailurePolicy::Retry && retries < SEND_RETRIES => {
+                    trace!("Retrying packet {} after: {}", index + 1, err);
+                    retries += 1;
+                    thread::sleep(SEND_RETRY_DELAY);
+                }
+                Err(err) => {
+                    return Err(PlayerError::SendError { packet: index + 1, reason: err.to_string() });
+                }
+            }
+        }
+    }
+
+    /// Sends the error to the frontend, and hands it back for returning.
+    fn report(&self, error: PlayerError) -> PlayerError {
+        let _ = self.event_tx.send(Event::error(error.clone()));
+        error
     }
 
     pub fn builder() -> PlayerBuilder {
         PlayerBuilder {
             recording: None,
             destination: None,
+            destination_mode: DestinationMode::default(),
+            mappings: Vec::new(),
+            filter: PacketFilter::default(),
+            bpf: None,
+            speed: PlaybackSpeed::default(),
+            repeat: Repeat::default(),
+            send_failure: SendFailurePolicy::default(),
+            sink: None,
+            simulated_start: None,
+            bind_address: None,
             source_port: None,
             ttl: None,
+            multicast: None,
             cmd_rx: None,
             event_tx: None,
         }
     }
 }
 
-fn duration_from_timestamp(mode: &PcapMagicNumber, packet: &PcapPacketRecord) -> Duration {
-    let (fraction, overflow) = match mode {
-        PcapMagicNumber::LeMicros => {
-            packet.ts_secs_fraction.overflowing_mul(1_000)
-        }
-        PcapMagicNumber::BeNanos => { (packet.ts_secs_fraction, false) }
-    };
-    let seconds = if overflow {
-        packet.ts_secs + 1
-    } else {
-        packet.ts_secs
-    } as u64;
-    Duration::new(seconds, fraction)
-}
-
 pub struct PlayerBuilder {
     recording: Option<Recording>,
     destination: Option<SocketAddr>,
+    destination_mode: DestinationMode,
+    mappings: Vec<DestinationMapping>,
+    filter: PacketFilter,
+    bpf: Option<BpfFilter>,
+    speed: PlaybackSpeed,
+    repeat: Repeat,
+    send_failure: SendFailurePolicy,
+    sink: Option<Box<dyn PacketSink>>,
+    simulated_start: Option<OutputStart>,
+    bind_address: Option<BindAddress>,
     source_port: Option<u16>,
     ttl: Option<u32>,
+    multicast: Option<MulticastOptions>,
     cmd_rx: Option<Receiver<Command>>,
     event_tx: Option<Sender<Event>>,
 }
@@ -236,6 +598,81 @@ impl PlayerBuilder {
         }
     }
 
+    pub fn destination_mode(self, destination_mode: DestinationMode) -> Self {
+        Self {
+            destination_mode,
+            ..self
+        }
+    }
+
+    pub fn mappings(self, mappings: Vec<DestinationMapping>) -> Self {
+        Self {
+            mappings,
+            ..self
+        }
+    }
+
+    pub fn filter(self, filter: PacketFilter) -> Self {
+        Self {
+            filter,
+            ..self
+        }
+    }
+
+    pub fn bpf(self, bpf: Option<BpfFilter>) -> Self {
+        Self {
+            bpf,
+            ..self
+        }
+    }
+
+    /// Sets the playback speed, clamped to `MIN_SPEED..=MAX_SPEED`.
+    pub fn speed(self, speed: PlaybackSpeed) -> Self {
+        Self {
+            speed: speed.clamped(),
+            ..self
+        }
+    }
+
+    pub fn repeat(self, repeat: Repeat) -> Self {
+        Self {
+            repeat,
+            ..self
+        }
+    }
+
+    pub fn send_failure(self, send_failure: SendFailurePolicy) -> Self {
+        Self {
+            send_failure,
+            ..self
+        }
+    }
+
+    /// Replays to the sink instead of a UDP socket bound to the source port.
+    pub fn sink(self, sink: impl PacketSink + 'static) -> Self {
+        Self {
+            sink: Some(Box::new(sink)),
+            ..self
+        }
+    }
+
+    /// Replays as fast as possible, stamping each packet with the time it would have been sent
+    /// as if the playback started at `start`. Meant for sinks that write to a file.
+    pub fn simulate(self, start: OutputStart) -> Self {
+        Self {
+            simulated_start: Some(start),
+            ..self
+        }
+    }
+
+    /// Sends from the local address only, instead of from the unspecified IPv4 and IPv6 addresses.
+    pub fn bind_address(self, bind_address: Option<BindAddress>) -> Self {
+        Self {
+            bind_address,
+            ..self
+        }
+    }
+
     pub fn source_port(self, source_port: u16) -> Self {
         Self {
             source_port : Some(source_port),
@@ -250,6 +687,14 @@ impl PlayerBuilder {
         }
     }
 
+    /// Sets how multicast datagrams are sent; by default with the TTL and from the system default interface.
+    pub fn multicast(self, multicast: MulticastOptions) -> Self {
+        Self {
+            multicast : Some(multicast),
+            ..self
+        }
+    }
+
     pub fn cmd_rx(self, cmd_rx: Receiver<Command>) -> Self {
         Self {
             cmd_rx : Some(cmd_rx),
@@ -264,28 +709,167 @@ impl PlayerBuilder {
         }
     }
 
-    pub fn build(self) -> Result<JoinHandle<()>, PlayerError> {
+    pub fn build(self) -> Result<JoinHandle<Result<(), PlayerError>>, PlayerError> {
         if self.recording.is_none() ||
             self.destination.is_none() ||
-            self.source_port.is_none() ||
-            self.ttl.is_none() ||
+            (self.sink.is_none() && (self.source_port.is_none() || self.ttl.is_none())) ||
             self.cmd_rx.is_none() ||
             self.event_tx.is_none() {
             return Err(PlayerError::PlayerInitError)
         }
         let mut player = Player {
             recording: self.recording.unwrap(),
-            destination: self.destination.unwrap(),
-            source_port: self.source_port.unwrap(),
-            ttl: self.ttl.unwrap(),
+            destinations: Destinations::new(self.destination.unwrap(), self.destination_mode, &self.mappings),
+            filter: self.filter,
+            bpf: self.bpf,
+            bind_address: self.bind_address,
+            source_port: self.source_port.unwrap_or(DEFAULT_SRC_PORT),
+            ttl: self.ttl.unwrap_or(DEFAULT_TTL),
+            multicast: self.multicast.unwrap_or_else(|| MulticastOptions::new(self.ttl.unwrap_or(DEFAULT_TTL))),
+            speed: self.speed,
+            repeat: self.repeat,
+            send_failure: self.send_failure,
+            sink: self.sink,
+            simulated_start: self.simulated_start,
             state: PlayerState::Initial,
             cmd_rx: self.cmd_rx.unwrap(),
             event_tx: self.event_tx.unwrap(),
         };
         Ok(thread::spawn(move || {
-            player.run();
+            player.run()
         }))
     }
 }
 
-// TODO progress bar elapsed time progresses even while paused; replace with time calculation based on the timestamps
\ No newline at end of file
+// TODO progress bar elapsed time progresses even while paused; replace with time calculation based on the timestamps
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use crate::repeat::{RepeatSection, Repetitions};
+    use crate::sinks::{ChannelSink, PcapSink};
+    use super::*;
+
+    /// A recording of packets at the given seconds, with the packet number as payload.
+    fn recording(name: &str, seconds: &[u64]) -> Recording {
+        let path = std::env::temp_dir().join(format!("packet-play-player-{name}-{}.pcap", std::process::id()));
+        let mut sink = PcapSink::create(&path).unwrap();
+        for (number, secs) in (1..).zip(seconds) {
+            sink.send(&SinkPacket {
+                timestamp: Duration::from_secs(1_000 + secs),
+                sent_at: Duration::ZERO,
+                source: "10.0.0.1:3000".parse().unwrap(),
+                destination: "10.0.0.2:4000".parse().unwrap(),
+                payload: &[number],
+                captured: None,
+            }).unwrap();
+        }
+        drop(sink);
+        let recording = Recording::try_from(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        recording
+    }
+
+    /// Plays the recording with the repeat to the end after the commands, returning the payloads sent.
+    fn play(recording: Recording, repeat: Repeat, commands: Vec<Command>) -> Vec<u8> {
+        let (sink, replayed) = ChannelSink::new();
+        let (cmd_sender, cmd_receiver) = mpsc::channel();
+        let (event_sender, event_receiver) = mpsc::channel();
+        let player = Player::builder()
+            .recording(recording)
+            .destination("127.0.0.1:5000".parse().unwrap())
+            .speed(PlaybackSpeed::Unthrottled)
+            .repeat(repeat)
+            .sink(sink)
+            .cmd_rx(cmd_receiver)
+            .event_tx(event_sender)
+            .build().unwrap();
+        commands.into_iter().for_each(|command| cmd_sender.send(command).unwrap());
+        cmd_sender.send(Command::Play).unwrap();
+        event_receiver.iter()
+            .find(|event| matches!(event, Event::PlayerStateChanged(change) if change.state == PlayerState::Finished))
+            .expect("The player did not finish");
+        cmd_sender.send(Command::Quit).unwrap();
+        player.join().unwrap().unwrap();
+        replayed.try_iter().map(|packet| packet.payload[0]).collect()
+    }
+
+    #[test]
+    fn repeat_section_from_its_start() {
+        let repeat = Repeat {
+            repetitions: Repetitions::Times(2),
+            section: Some("3..5".parse::<RepeatSection>().unwrap()),
+            gap: Duration::ZERO,
+        };
+        assert_eq!(play(recording("section", &[1, 2, 3, 4, 5, 6, 7, 8]), repeat, Vec::new()), vec![3, 4, 5, 3, 4, 5]);
+    }
+
+    #[test]
+    fn report_steps_past_either_end() {
+        let (sink, replayed) = ChannelSink::new();
+        let (cmd_sender, cmd_receiver) = mpsc::channel();
+        let (event_sender, event_receiver) = mpsc::channel();
+        let player = Player::builder()
+            .recording(recording("steps", &[0, 1]))
+            .destination("127.0.0.1:5000".parse().unwrap())
+            .sink(sink)
+            .cmd_rx(cmd_receiver)
+            .event_tx(event_sender)
+            .build().unwrap();
+        for command in [Command::StepForward(0), Command::StepBackward(1), Command::StepForward(2), Command::StepForward(1),
+                        Command::Quit] {
+            cmd_sender.send(command).unwrap();
+        }
+        player.join().unwrap().unwrap();
+
+        let errors: Vec<PlayerError> = event_receiver.try_iter().filter_map(|event| match event {
+            Event::Error(error) => { Some(error) }
+            _ => { None }
+        }).collect();
+        assert!(matches!(errors[..], [PlayerError::SeekPositionOutOfRange { position: 0, packets: 2 },
+                                      PlayerError::SeekPositionOutOfRange { position: 2, packets: 2 }]));
+        assert_eq!(replayed.try_iter().map(|packet| packet.payload[0]).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn step_within_section() {
+        let (sink, replayed) = ChannelSink::new();
+        let (cmd_sender, cmd_receiver) = mpsc::channel();
+        let (event_sender, event_receiver) = mpsc::channel();
+        let player = Player::builder()
+            .recording(recording("section-steps", &[0, 1, 2, 3, 4]))
+            .destination("127.0.0.1:5000".parse().unwrap())
+            .repeat(Repeat { section: Some("2..3".parse().unwrap()), ..Repeat::default() })
+            .sink(sink)
+            .cmd_rx(cmd_receiver)
+            .event_tx(event_sender)
+            .build().unwrap();
+        for command in [Command::StepForward(5), Command::StepForward(1), Command::Quit] {
+            cmd_sender.send(command).unwrap();
+        }
+        player.join().unwrap().unwrap();
+
+        let errors: Vec<PlayerError> = event_receiver.try_iter().filter_map(|event| match event {
+            Event::Error(error) => { Some(error) }
+            _ => { None }
+        }).collect();
+        assert!(matches!(errors[..], [PlayerError::SeekPositionOutOfRange { position: 3, packets: 3 }]));
+        assert_eq!(replayed.try_iter().map(|packet| packet.payload[0]).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn seek_time_in_recording_out_of_order() {
+        // Seeking to 3s goes to the first packet in order at or after it, the third.
+        let recording = recording("unsorted", &[0, 1, 4, 2, 3, 5]);
+        let seek = vec![Command::SeekTime(Duration::from_secs(3))];
+        assert_eq!(play(recording, Repeat::default(), seek), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn clamp_and_scale_speeds() {
+        assert_eq!(PlaybackSpeed::Multiplier(1e-30).clamped(), PlaybackSpeed::Multiplier(MIN_SPEED));
+        assert_eq!(PlaybackSpeed::Multiplier(f64::INFINITY).clamped(), PlaybackSpeed::Multiplier(MAX_SPEED));
+        assert_eq!(PlaybackSpeed::Multiplier(f64::NAN).clamped(), PlaybackSpeed::default());
+        assert_eq!(PlaybackSpeed::Multiplier(2.0).scale(Duration::from_secs(3)), Duration::from_millis(1500));
+        assert_eq!(PlaybackSpeed::Multiplier(0.0).scale(Duration::from_secs(1)), Duration::MAX);
+    }
+}
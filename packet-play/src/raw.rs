@@ -0,0 +1,331 @@
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use packet_rehash_core::sink::PacketSink;
+use pcap_files::{decode_packet, IpPacket, LinkHeader, ETHERNET_HEADER_LENGTH_BYTES, IP_HEADER_LENGTH_BYTES, IP_PROTOCOL_UDP, LINKTYPE_ETHERNET};
+use serde_derive::Serialize;
+
+use crate::sinks::{checksum, udp_checksum};
+
+const VLAN_TAG_LENGTH_BYTES : usize = 4;
+
+/// Re-emits the captured Ethernet frames of the packets on a network interface, through an `AF_PACKET` socket,
+/// keeping their original link and IP headers apart from the rewritten fields. The destination of the packets
+/// is not used. Sending needs `CAP_NET_RAW`; a veth pair or a TAP device makes a local receiver.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct RawSink {
+    socket: std::os::fd::OwnedFd,
+    rewrites: Vec<FrameRewrite>,
+    checksums: bool,    // recalculate the checksums even when no IP field is rewritten
+}
+
+/// A MAC address, written as six hexadecimal octets separated by colons, e.g. `02:00:5e:10:00:01`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MacAddress(pub [u8; 6]);
+
+/// A field of a captured frame to replace before it is re-emitted,
+/// written as `src-mac=MAC`, `dst-mac=MAC`, `src-ip=ADDR` or `dst-ip=ADDR`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum FrameRewrite {
+    SourceMac(MacAddress),
+    DestinationMac(MacAddress),
+    SourceIp(IpAddr),           // of the same IP version as the packets
+    DestinationIp(IpAddr),
+}
+
+#[cfg(target_os = "linux")]
+impl RawSink {
+    /// Opens a packet socket bound to the interface, e.g. `eth0`.
+    pub fn open(interface: &str) -> Result<Self, Error> {
+        use std::os::fd::FromRawFd;
+
+        let index = crate::multicast::interface_index(interface)?;
+        // Protocol zero, so the socket receives nothing and only sends.
+        let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        let socket = unsafe { std::os::fd::OwnedFd::from_raw_fd(fd) };
+
+        let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as u16;
+        address.sll_ifindex = index as i32;
+        let bound = unsafe {
+            libc::bind(fd, &address as *const libc::sockaddr_ll as *const libc::sockaddr,
+                       std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t)
+        };
+        if bound < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(Self {
+            socket,
+            rewrites: Vec::new(),
+            checksums: false,
+        })
+    }
+
+    pub fn with_rewrite(mut self, rewrite: FrameRewrite) -> Self {
+        self.rewrites.push(rewrite);
+        self
+    }
+
+    /// Recalculates the IP and UDP checksums of every frame, such as for captures with offloaded checksums.
+    /// Rewriting an IP address always recalculates them.
+    pub fn with_checksums(mut self) -> Self {
+        self.checksums = true;
+        self
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl PacketSink for RawSink {
+    fn send(&mut self, packet: &packet_rehash_core::sink::SinkPacket) -> Result<(), Error> {
+        use std::os::fd::AsRawFd;
+
+        let captured = packet.captured
+            .ok_or_else(|| Error::new(ErrorKind::Unsupported, "A datagram reassembled from fragments has no single frame to re-emit"))?;
+        if captured.link_type != LINKTYPE_ETHERNET {
+            return Err(Error::new(ErrorKind::Unsupported,
+                format!("Cannot re-emit frames of link type {}, only Ethernet", captured.link_type)));
+        }
+        let frame = if self.rewrites.is_empty() && !self.checksums {
+            std::borrow::Cow::Borrowed(captured.data)
+        } else {
+            std::borrow::Cow::Owned(rewrite_frame(captured.data, &self.rewrites, self.checksums)?)
+        };
+        let sent = unsafe { libc::send(self.socket.as_raw_fd(), frame.as_ptr() as *const libc::c_void, frame.len(), 0) };
+        if sent < 0 {
+            Err(Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Opens a `RawSink` on the interface with the rewrites, or fails on platforms other than Linux.
+pub fn raw_sink(interface: &str, rewrites: &[FrameRewrite], checksums: bool) -> Result<Box<dyn PacketSink>, Error> {
+    #[cfg(target_os = "linux")]
+    {
+        let sink = rewrites.iter().fold(RawSink::open(interface)?, |sink, rewrite| sink.with_rewrite(*rewrite));
+        Ok(Box::new(if checksums { sink.with_checksums() } else { sink }))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (interface, rewrites, checksums);
+        Err(Error::new(ErrorKind::Unsupported, "Raw replay is only supported on Linux"))
+    }
+}
+
+/// Copies the Ethernet frame with the fields rewritten, recalculating the IPv4 header checksum and the UDP checksum
+/// when an IP address is rewritten or `checksums` is set. The UDP checksum of a fragment or a truncated capture is left as is.
+pub(crate) fn rewrite_frame(data: &[u8], rewrites: &[FrameRewrite], checksums: bool) -> Result<Vec<u8>, Error> {
+    let decoded = decode_packet(LINKTYPE_ETHERNET, data)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, format!("Cannot rewrite the frame: {err}")))?;
+    let LinkHeader::Ethernet(ethernet) = &decoded.link else {
+        return Err(Error::new(ErrorKind::InvalidData, "Cannot rewrite a frame other than Ethernet"));
+    };
+    let ip_start = ETHERNET_HEADER_LENGTH_BYTES as usize + VLAN_TAG_LENGTH_BYTES * ethernet.vlan_tags.len();
+    let ipv4 = matches!(decoded.ip, IpPacket::V4(_));
+    // The payload borrows from the frame, so its offset is where the IP header and any extension headers end.
+    let payload = decoded.ip.payload();
+    let payload_start = payload.as_ptr() as usize - data.as_ptr() as usize;
+    let udp_length = payload.get(4..6).map(|length| u16::from_be_bytes([length[0], length[1]]) as usize);
+    let complete_udp = decoded.ip.protocol() == IP_PROTOCOL_UDP && !decoded.ip.is_fragment() && udp_length == Some(payload.len());
+
+    let mut frame = data.to_vec();
+    let mut recalculate = checksums;
+    for rewrite in rewrites {
+        match rewrite {
+            FrameRewrite::DestinationMac(mac) => { frame[0..6].copy_from_slice(&mac.0) }
+            FrameRewrite::SourceMac(mac) => { frame[6..12].copy_from_slice(&mac.0) }
+            FrameRewrite::SourceIp(address) | FrameRewrite::DestinationIp(address) => {
+                let source = matches!(rewrite, FrameRewrite::SourceIp(_));
+                let (offset, octets) = match (address, ipv4) {
+                    (IpAddr::V4(address), true) => { (if source { 12 } else { 16 }, address.octets().to_vec()) }
+                    (IpAddr::V6(address), false) => { (if source { 8 } else { 24 }, address.octets().to_vec()) }
+                    _ => {
+                        return Err(Error::new(ErrorKind::InvalidInput,
+                            format!("Cannot rewrite an address of {} packet to {address}", if ipv4 { "an IPv4" } else { "an IPv6" })));
+                    }
+                };
+                let start = ip_start + offset;
+                frame[start..start + octets.len()].copy_from_slice(&octets);
+                recalculate = true;
+            }
+        }
+    }
+
+    if recalculate {
+        let addresses = if let IpPacket::V4(ip) = &decoded.ip {
+            let header_end = ip_start + IP_HEADER_LENGTH_BYTES as usize + ip.options.len();
+            frame[ip_start + 10..ip_start + 12].fill(0);
+            let header_checksum = checksum(&frame[ip_start..header_end]);
+            frame[ip_start + 10..ip_start + 12].copy_from_slice(&header_checksum.to_be_bytes());
+            ip_start + 12..ip_start + 20
+        } else {
+            ip_start + 8..ip_start + 40
+        };
+        if complete_udp {
+            let udp = payload_start..payload_start + payload.len();
+            frame[udp.start + 6..udp.start + 8].fill(0);
+            let (source, destination) = frame[addresses.clone()].split_at(addresses.len() / 2);
+            let udp_checksum = udp_checksum(source, destination, &frame[udp.clone()]);
+            frame[udp.start + 6..udp.start + 8].copy_from_slice(&udp_checksum.to_be_bytes());
+        }
+    }
+    Ok(frame)
+}
+
+impl FromStr for MacAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid MAC address `{s}`, expected six hexadecimal octets like 02:00:5e:10:00:01");
+        let octets = s.trim().split([':', '-'])
+            .map(|octet| if octet.len() == 2 { u8::from_str_radix(octet, 16).ok() } else { None })
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+        Ok(Self(octets.try_into().map_err(|_| invalid())?))
+    }
+}
+
+impl Display for MacAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+impl FromStr for FrameRewrite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, value) = s.split_once('=')
+            .ok_or_else(|| format!("Expected FIELD=VALUE, found `{s}`"))?;
+        let address = |value: &str| value.trim().parse::<IpAddr>()
+            .map_err(|err| format!("Invalid IP address `{value}`: {err}"));
+        match field.trim() {
+            "src-mac" => { Ok(FrameRewrite::SourceMac(value.parse()?)) }
+            "dst-mac" => { Ok(FrameRewrite::DestinationMac(value.parse()?)) }
+            "src-ip" => { Ok(FrameRewrite::SourceIp(address(value)?)) }
+            "dst-ip" => { Ok(FrameRewrite::DestinationIp(address(value)?)) }
+            other => { Err(format!("Unknown field `{other}`, expected src-mac, dst-mac, src-ip or dst-ip")) }
+        }
+    }
+}
+
+impl Display for FrameRewrite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameRewrite::SourceMac(mac) => { write!(f, "src-mac={mac}") }
+            FrameRewrite::DestinationMac(mac) => { write!(f, "dst-mac={mac}") }
+            FrameRewrite::SourceIp(address) => { write!(f, "src-ip={address}") }
+            FrameRewrite::DestinationIp(address) => { write!(f, "dst-ip={address}") }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use super::*;
+
+    /// An Ethernet frame with a VLAN tag, carrying an IPv4/UDP packet from 10.0.0.1:3000 to 239.1.2.3:3001.
+    fn tagged_frame() -> Vec<u8> {
+        let mut frame = vec![0x01, 0x00, 0x5e, 0x01, 0x02, 0x03, 0x02, 0, 0, 0, 0, 0x01, 0x81, 0x00, 0x00, 0x64, 0x08, 0x00];
+        frame.extend_from_slice(&[0x45, 0, 0, 31, 0, 0, 0, 0, 64, IP_PROTOCOL_UDP, 0, 0, 10, 0, 0, 1, 239, 1, 2, 3]);
+        frame.extend_from_slice(&[0x0b, 0xb8, 0x0b, 0xb9, 0, 11, 0, 0, b'a', b'b', b'c']);
+        frame.extend_from_slice(&[0; 6]);   // padding to the minimum frame size
+        frame
+    }
+
+    #[test]
+    fn rewrite_addresses_and_checksums() {
+        let frame = tagged_frame();
+        let rewrites = ["dst-mac=02:00:5e:10:00:01", "src-ip=192.168.1.7"].map(|rewrite| rewrite.parse::<FrameRewrite>().unwrap());
+        let rewritten = rewrite_frame(&frame, &rewrites, false).unwrap();
+        assert_eq!(rewritten.len(), frame.len());
+        assert_eq!(rewritten[0..6], [0x02, 0x00, 0x5e, 0x10, 0x00, 0x01]);
+        assert_eq!(rewritten[6..12], frame[6..12]);
+
+        let packet = decode_packet(LINKTYPE_ETHERNET, &rewritten).unwrap();
+        assert_eq!(packet.ip.source(), IpAddr::V4(Ipv4Addr::new(192, 168, 1, 7)));
+        assert_eq!(packet.udp.unwrap().payload, b"abc");
+        assert_eq!(checksum(&rewritten[18..38]), 0);
+        // A valid UDP checksum sums to zero together with the pseudo header.
+        let mut udp = rewritten[38..49].to_vec();
+        let sent = u16::from_be_bytes([udp[6], udp[7]]);
+        udp[6..8].fill(0);
+        assert_eq!(udp_checksum(&rewritten[30..34], &rewritten[34..38], &udp), sent);
+
+        assert!(rewrite_frame(&frame, &["dst-ip=ff02::1".parse().unwrap()], false).is_err());
+        assert_eq!(rewrite_frame(&frame, &[], false).unwrap(), frame);
+    }
+
+    #[test]
+    fn parse_rewrites() {
+        assert_eq!("src-mac=02:00:5E:10:00:01".parse(), Ok(FrameRewrite::SourceMac(MacAddress([2, 0, 0x5e, 0x10, 0, 1]))));
+        assert_eq!("dst-ip=10.0.0.9".parse(), Ok(FrameRewrite::DestinationIp(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 9)))));
+        assert_eq!(FrameRewrite::SourceMac(MacAddress([2, 0, 0x5e, 0x10, 0, 1])).to_string(), "src-mac=02:00:5e:10:00:01");
+        assert!("src-mac=02:00:5e:10:00".parse::<FrameRewrite>().is_err());
+        assert!("ttl=4".parse::<FrameRewrite>().is_err());
+    }
+
+    /// Re-emits a frame on one end of a veth pair and receives it on the other, e.g. after
+    /// `ip link add veth0 type veth peer name veth1 && ip link set veth0 up && ip link set veth1 up`.
+    #[test]
+    #[ignore = "needs CAP_NET_RAW and a veth pair named by PACKET_PLAY_VETH=veth0,veth1"]
+    #[cfg(target_os = "linux")]
+    fn re_emit_frame_on_veth_pair() {
+        use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+        use packet_rehash_core::sink::{CapturedFrame, SinkPacket};
+
+        let interfaces = std::env::var("PACKET_PLAY_VETH").expect("PACKET_PLAY_VETH is not set");
+        let (sender, receiver) = interfaces.split_once(',').expect("Expected PACKET_PLAY_VETH=IF0,IF1");
+
+        // Receive everything on the other end, with a timeout.
+        let protocol = (libc::ETH_P_ALL as u16).to_be();
+        let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, protocol as i32) };
+        assert!(fd >= 0, "{}", Error::last_os_error());
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+        let name = std::ffi::CString::new(receiver).unwrap();
+        let mut address: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as u16;
+        address.sll_protocol = protocol;
+        address.sll_ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) } as i32;
+        let timeout = libc::timeval { tv_sec: 1, tv_usec: 0 };
+        unsafe {
+            assert_eq!(libc::bind(fd, &address as *const libc::sockaddr_ll as *const libc::sockaddr,
+                                  std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t), 0);
+            libc::setsockopt(fd, libc::SOL_SOCKET, libc::SO_RCVTIMEO, &timeout as *const libc::timeval as *const libc::c_void,
+                             std::mem::size_of::<libc::timeval>() as libc::socklen_t);
+        }
+
+        let mut sink = RawSink::open(sender).unwrap().with_rewrite("src-ip=10.9.9.9".parse().unwrap());
+        let frame = tagged_frame();
+        sink.send(&SinkPacket {
+            timestamp: Default::default(),
+            sent_at: Default::default(),
+            source: SocketAddr::new(Ipv4Addr::new(10, 0, 0, 1).into(), 3000),
+            destination: SocketAddr::new(Ipv4Addr::new(239, 1, 2, 3).into(), 3001),
+            payload: b"abc",
+            captured: Some(CapturedFrame { link_type: LINKTYPE_ETHERNET, data: &frame }),
+        }).unwrap();
+
+        let mut buffer = [0u8; 2048];
+        loop {
+            let length = unsafe { libc::recv(socket.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), 0) };
+            assert!(length >= 0, "Nothing received: {}", Error::last_os_error());
+            let Ok(packet) = decode_packet(LINKTYPE_ETHERNET, &buffer[..length as usize]) else { continue };
+            if packet.udp.is_some_and(|udp| udp.payload == b"abc") {
+                assert_eq!(packet.ip.source(), IpAddr::V4(Ipv4Addr::new(10, 9, 9, 9)));
+                assert_eq!(buffer[6..12], frame[6..12]);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use log::{trace, warn};
+use pcap_files::{decode_packet, BpfFilter, FragmentReassembler, MappedPcap, PcapNG, IP_PROTOCOL_UDP};
+
+use crate::FileError;
+use crate::filter::{PacketFilter, PacketInfo};
+
+const SUPPORTED_EXTENSIONS: [&str; 2] = ["pcap", "pcapng"];
+const PCAPNG_MAGIC: [u8; 4] = [0x0A, 0x0D, 0x0D, 0x0A];
+
+#[derive(Debug)]
+pub enum Recording {
+    Pcap(MappedPcap),
+    PcapNg(PcapNG),
+}
+
+/// A single captured packet as replayed by the `Player`, independent of the file format of the recording.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Frame<'a> {
+    pub timestamp: Duration,
+    pub link_type: u32,
+    pub data: &'a [u8],
+}
+
+/// The payload of a UDP datagram as replayed by the `Player`. Datagrams that were fragmented in the recording
+/// are reassembled, and replayed at the timestamp of their last fragment.
+#[derive(Clone, Debug)]
+pub(crate) struct Datagram<'a> {
+    pub timestamp: Duration,
+    pub source: SocketAddr,         // the captured source address and port
+    pub destination: SocketAddr,    // the captured destination address and port
+    pub payload: Cow<'a, [u8]>,
+    pub frame: Option<Frame<'a>>,   // the captured frame, unless reassembled from fragments
+}
+
+impl Recording {
+    /// Iterates all packets of the recording in order, with timestamps relative to the epoch.
+    /// Packets without a timestamp (pcapng Simple Packet Blocks) get the timestamp of the preceding packet.
+    /// The frames borrow the packet data from the recording, which for .pcap files is memory-mapped.
+    /// Damaged regions of a .pcap file are skipped, as reported when loading the recording.
+    pub(crate) fn frames(&self) -> Box<dyn Iterator<Item = Frame<'_>> + '_> {
+        match self {
+            Recording::Pcap(pcap) => {
+                let header = pcap.header();
+                Box::new(pcap.packets_lenient().filter_map(Result::ok).map(|packet| Frame {
+                    timestamp: header.timestamp(packet.ts_secs, packet.ts_secs_fraction),
+                    link_type: header.link_type,
+                    data: packet.packet_data,
+                }))
+            }
+            Recording::PcapNg(pcapng) => {
+                let mut previous_ts = Duration::ZERO;
+                Box::new(pcapng.packets().map(move |packet| {
+                    let timestamp = packet.timestamp.unwrap_or(previous_ts);
+                    previous_ts = timestamp;
+                    Frame {
+                        timestamp,
+                        link_type: packet.interface.link_type as u32,
+                        data: packet.packet_data,
+                    }
+                }))
+            }
+        }
+    }
+
+    /// Decodes the UDP datagrams of the recording that match the filters, skipping all other packets and
+    /// reporting fragmented datagrams that cannot be reassembled. The `bpf` filter is evaluated on the captured
+    /// packets, so a fragmented datagram matches when any of its fragments matches.
+    pub(crate) fn datagrams(&self, filter: &PacketFilter, bpf: Option<&BpfFilter>) -> Vec<Datagram<'_>> {
+        let mut frames = self.frames().peekable();
+        let first_ts = frames.peek().map(|frame| frame.timestamp).unwrap_or_default();
+        let mut reassembler = FragmentReassembler::new();
+        let mut matched_fragments = HashSet::new();
+        let mut datagrams = Vec::new();
+        for (i, frame) in frames.enumerate() {
+            let mut bpf_matches = bpf.is_none_or(|bpf| bpf.matches(frame.link_type, frame.data));
+            let packet = match decode_packet(frame.link_type, frame.data) {
+                Ok(packet) => { packet }
+                Err(err) => {
+                    trace!("Skipping packet {}: {}", i + 1, err);
+                    continue;
+                }
+            };
+            let mut info = PacketInfo {
+                index: i,
+                time: frame.timestamp.saturating_sub(first_ts),
+                source: packet.ip.source(),
+                destination: packet.ip.destination(),
+                protocol: packet.ip.protocol(),
+                ports: None,
+                length: packet.ip.payload().len(),
+            };
+            let reassembled = if packet.udp.is_none() { reassembler.push(frame.timestamp, &packet.ip) } else { None };
+            for incomplete in reassembler.take_incomplete() {
+                matched_fragments.remove(&(incomplete.source, incomplete.destination, incomplete.protocol, incomplete.identification));
+                warn!("Skipping fragmented {incomplete}");
+            }
+            // Keyed like the reassembler, so a fragment only marks its own datagram as matched.
+            let fragment_key = packet.ip.fragment()
+                .map(|fragment| (info.source, info.destination, info.protocol, fragment.identification));
+            let (payload, captured) = if let Some(udp) = packet.udp {
+                info.ports = Some((udp.source_port, udp.destination_port));
+                (Cow::Borrowed(udp.payload), Some(frame))
+            } else if let Some(reassembled) = reassembled {
+                let matched_before = fragment_key.is_some_and(|key| matched_fragments.remove(&key));
+                bpf_matches = bpf_matches || matched_before;
+                match reassembled.udp() {
+                    Ok(Some(udp)) => {
+                        info.protocol = IP_PROTOCOL_UDP;
+                        info.ports = Some((udp.source_port, udp.destination_port));
+                        info.length = reassembled.payload.len();
+                        (Cow::Owned(udp.payload.to_vec()), None)
+                    }
+                    Ok(None) => {
+                        trace!("Skipping packet {} that completes a datagram other than UDP", i + 1);
+                        continue;
+                    }
+                    Err(err) => {
+                        trace!("Skipping datagram completed by packet {}: {}", i + 1, err);
+                        continue;
+                    }
+                }
+            } else {
+                match fragment_key {
+                    Some(key) if bpf_matches => { matched_fragments.insert(key); }
+                    Some(_) => {}
+                    None => { trace!("Skipping packet {} that is not a UDP datagram", i + 1); }
+                }
+                continue;
+            };
+
+            if bpf_matches && filter.matches(&info) {
+                let (source_port, destination_port) = info.ports.expect("UDP datagrams have ports.");
+                datagrams.push(Datagram {
+                    timestamp: frame.timestamp,
+                    source: SocketAddr::new(info.source, source_port),
+                    destination: SocketAddr::new(info.destination, destination_port),
+                    payload,
+                    frame: captured,
+                });
+            }
+        }
+        reassembler.finish().iter().for_each(|incomplete| warn!("Skipping fragmented {incomplete}"));
+        datagrams
+    }
+}
+
+impl TryFrom<&str> for Recording {
+    type Error = FileError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let file_path = std::path::Path::new(value);
+        if !file_path.exists() {
+            return Err(FileError::PathDoesNotExist);
+        };
+        if !file_path.is_file() {
+            return Err(FileError::NotAFile);
+        }
+
+        match file_path.extension().and_then(OsStr::to_str) {
+            None => { return Err(FileError::FileTypeNotSupported(String::from(""))); }
+            Some(os_str) => {
+                if !SUPPORTED_EXTENSIONS.contains(&os_str) {
+                    return Err(FileError::FileTypeNotSupported(os_str.to_string()));
+                }
+            }
+        }
+
+        let mut file = File::open(file_path).map_err(|err| FileError::ReadError(err.to_string()))?;
+
+        // Tools do not always use the matching extension, so detect the actual format from the first bytes.
+        let mut magic = [0u8; 4];
+        let is_pcapng = file.read_exact(&mut magic).is_ok() && magic == PCAPNG_MAGIC;
+        file.rewind().map_err(|err| FileError::ReadError(err.to_string()))?;
+
+        // Recordings are often cut off or partially damaged; play what can be recovered.
+        let (recording, has_packets) = if is_pcapng {
+            let (pcapng, damage) = PcapNG::parse_file_lenient(&file).map_err(FileError::ParseError)?;
+            damage.iter().for_each(|err| warn!("Skipped damaged part of the recording: {err}"));
+            let has_packets = pcapng.packets().next().is_some();
+            (Recording::PcapNg(pcapng), has_packets)
+        } else {
+            let pcap = MappedPcap::try_from(file).map_err(FileError::ParseError)?;
+            let mut has_packets = false;
+            for packet in pcap.packets_lenient() {
+                match packet {
+                    Ok(_) => { has_packets = true; }
+                    Err(err) => { warn!("Skipped damaged part of the recording: {err}"); }
+                }
+            }
+            (Recording::Pcap(pcap), has_packets)
+        };
+
+        if !has_packets {
+            return Err(FileError::NoPackets);
+        }
+        Ok(recording)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pcap_files::{EnhancedPacketBlock, InterfaceDescriptionBlock, PcapNgWriter, SectionHeaderBlock, SimplePacketBlock,
+                     TimestampResolution, LINKTYPE_ETHERNET, LINKTYPE_RAW};
+
+    use super::*;
+    use crate::sinks::encode_ip_udp;
+
+    const IP_PROTOCOL_TCP: u8 = 6;
+
+    #[test]
+    fn datagrams_of_pcapng_interfaces() {
+        let packet = |payload: &[u8]| {
+            encode_ip_udp("10.0.0.1:3000".parse().unwrap(), "10.0.0.2:4000".parse().unwrap(), payload).unwrap()
+        };
+        let path = std::env::temp_dir().join(format!("packet-play-recording-{}.pcapng", std::process::id()));
+        let mut writer = PcapNgWriter::new(File::create(&path).unwrap(), &SectionHeaderBlock::new()).unwrap();
+        let raw_micros = writer.write_interface(&InterfaceDescriptionBlock::new(LINKTYPE_RAW as u16, 0)).unwrap();
+        let mut ethernet_nanos = InterfaceDescriptionBlock::new(LINKTYPE_ETHERNET as u16, 0);
+        ethernet_nanos.ts_resolution = TimestampResolution::NANOS;
+        let ethernet_nanos = writer.write_interface(&ethernet_nanos).unwrap();
+
+        writer.write_enhanced_packet(&EnhancedPacketBlock::new(raw_micros, 1_000_000_250, packet(b"first"))).unwrap();
+        let mut frame = vec![0; 12];
+        frame.extend_from_slice(&[0x08, 0x00]);
+        frame.extend(packet(b"second"));
+        writer.write_enhanced_packet(&EnhancedPacketBlock::new(ethernet_nanos, 1_000_000_500_001, frame)).unwrap();
+        // Simple Packet Blocks are of the first interface, and have no timestamp of their own.
+        let third = packet(b"third");
+        writer.write_simple_packet(&SimplePacketBlock { original_packet_length: third.len() as u32, packet_data: third }).unwrap();
+        drop(writer);
+
+        let recording = Recording::try_from(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let datagrams = recording.datagrams(&PacketFilter::default(), None);
+        let replayed: Vec<_> = datagrams.iter().map(|datagram| (datagram.timestamp, datagram.payload.as_ref())).collect();
+        assert_eq!(replayed, vec![
+            (Duration::new(1_000, 250_000), b"first".as_slice()),
+            (Duration::new(1_000, 500_001), b"second".as_slice()),
+            (Duration::new(1_000, 500_001), b"third".as_slice()),
+        ]);
+        assert_eq!(datagrams[1].frame.map(|frame| frame.link_type), Some(LINKTYPE_ETHERNET));
+        assert_eq!(datagrams[1].destination, "10.0.0.2:4000".parse().unwrap());
+    }
+
+    /// An IPv4 packet from 10.0.0.1 to 10.0.0.2 with a fragment of a datagram of the protocol.
+    fn ipv4_fragment(protocol: u8, identification: u16, offset: u16, more_fragments: bool, data: &[u8]) -> Vec<u8> {
+        let flags_and_offset = (offset >> 3) | if more_fragments { 0x2000 } else { 0 };
+        let mut packet = vec![0x45, 0];
+        packet.extend_from_slice(&(20 + data.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&identification.to_be_bytes());
+        packet.extend_from_slice(&flags_and_offset.to_be_bytes());
+        packet.extend_from_slice(&[64, protocol, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    #[test]
+    fn match_fragments_of_the_same_protocol_only() {
+        let datagram = encode_ip_udp("10.0.0.1:3000".parse().unwrap(), "10.0.0.2:4000".parse().unwrap(), &[7; 24]).unwrap();
+        let udp = &datagram[20..];
+        let path = std::env::temp_dir().join(format!("packet-play-recording-fragments-{}.pcapng", std::process::id()));
+        let mut writer = PcapNgWriter::new(File::create(&path).unwrap(), &SectionHeaderBlock::new()).unwrap();
+        let interface = writer.write_interface(&InterfaceDescriptionBlock::new(LINKTYPE_RAW as u16, 0)).unwrap();
+        for packet in [
+            ipv4_fragment(IP_PROTOCOL_TCP, 1, 0, true, &[0; 16]),
+            ipv4_fragment(IP_PROTOCOL_UDP, 1, 0, true, &udp[..16]),
+            ipv4_fragment(IP_PROTOCOL_UDP, 1, 16, false, &udp[16..]),
+        ] {
+            writer.write_enhanced_packet(&EnhancedPacketBlock::new(interface, 0, packet)).unwrap();
+        }
+        drop(writer);
+        let recording = Recording::try_from(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let filter = PacketFilter::default();
+        assert!(recording.datagrams(&filter, Some(&"tcp".parse().unwrap())).is_empty());
+        let datagrams = recording.datagrams(&filter, Some(&"udp".parse().unwrap()));
+        assert_eq!(datagrams.len(), 1);
+        assert_eq!(datagrams[0].payload.as_ref(), &[7; 24]);
+    }
+
+    #[test]
+    fn reject_recording_without_packets() {
+        let path = std::env::temp_dir().join(format!("packet-play-recording-empty-{}.pcapng", std::process::id()));
+        let mut writer = PcapNgWriter::new(File::create(&path).unwrap(), &SectionHeaderBlock::new()).unwrap();
+        writer.write_interface(&InterfaceDescriptionBlock::new(LINKTYPE_RAW as u16, 0)).unwrap();
+        drop(writer);
+
+        let recording = Recording::try_from(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(recording, Err(FileError::NoPackets)));
+    }
+}
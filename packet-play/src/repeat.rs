@@ -0,0 +1,177 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde_derive::Serialize;
+
+use crate::recording::Datagram;
+
+/// How often the `Player` plays the recording, or the repeated section of it.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize)]
+pub enum Repetitions {
+    #[default]
+    Once,
+    Times(u32),
+    Forever,
+}
+
+/// Where a repeated section starts or ends.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub enum RepeatPoint {
+    Packet(usize),      // the position of a packet, starting at 1
+    Time(Duration),     // the time since the first packet
+}
+
+/// A section of the recording to repeat, written as `A..B` with packet positions or times in seconds,
+/// e.g. `100..250` or `10s..25.5s`. Both ends are included.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct RepeatSection {
+    pub from: RepeatPoint,
+    pub to: RepeatPoint,
+}
+
+/// How the `Player` repeats the recording.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize)]
+pub struct Repeat {
+    pub repetitions: Repetitions,
+    pub section: Option<RepeatSection>,     // the whole recording when `None`
+    pub gap: Duration,                      // the pause between iterations
+}
+
+impl Repetitions {
+    /// Whether the given iteration, starting at 1, is to be played.
+    pub fn includes(&self, iteration: u32) -> bool {
+        match self {
+            Repetitions::Once => { iteration <= 1 }
+            Repetitions::Times(times) => { iteration <= *times }
+            Repetitions::Forever => { true }
+        }
+    }
+}
+
+impl Display for Repetitions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Repetitions::Once => { write!(f, "1") }
+            Repetitions::Times(times) => { write!(f, "{times}") }
+            Repetitions::Forever => { write!(f, "∞") }
+        }
+    }
+}
+
+impl RepeatSection {
+    /// The indices of the first and last datagram of the section, if it contains any. A section given by time
+    /// runs from the first datagram at or after A to the last one at or before B; unless the datagrams are
+    /// `sorted` by time, these are searched linearly.
+    pub(crate) fn resolve(&self, datagrams: &[Datagram], sorted: bool) -> Option<(usize, usize)> {
+        let first_ts = datagrams.first()?.timestamp;
+        let since_first = |datagram: &Datagram| datagram.timestamp.saturating_sub(first_ts);
+        let from = match self.from {
+            RepeatPoint::Packet(position) => { position.saturating_sub(1) }
+            RepeatPoint::Time(time) if sorted => { datagrams.partition_point(|datagram| since_first(datagram) < time) }
+            RepeatPoint::Time(time) => {
+                datagrams.iter().position(|datagram| since_first(datagram) >= time).unwrap_or(datagrams.len())
+            }
+        };
+        let to = match self.to {
+            RepeatPoint::Packet(position) => { position.min(datagrams.len()).checked_sub(1)? }
+            RepeatPoint::Time(time) if sorted => {
+                datagrams.partition_point(|datagram| since_first(datagram) <= time).checked_sub(1)?
+            }
+            RepeatPoint::Time(time) => { datagrams.iter().rposition(|datagram| since_first(datagram) <= time)? }
+        };
+        (from <= to).then_some((from, to))
+    }
+}
+
+impl FromStr for RepeatPoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_suffix('s') {
+            Some(seconds) => {
+                seconds.parse::<f64>().ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .map(RepeatPoint::Time)
+                    .ok_or_else(|| format!("Invalid time `{s}`"))
+            }
+            None => {
+                s.parse::<usize>().ok()
+                    .filter(|position| *position > 0)
+                    .map(RepeatPoint::Packet)
+                    .ok_or_else(|| format!("Invalid packet position `{s}`, positions start at 1"))
+            }
+        }
+    }
+}
+
+impl FromStr for RepeatSection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s.split_once("..")
+            .ok_or_else(|| format!("Expected A..B, found `{s}`"))?;
+        Ok(Self {
+            from: from.parse()?,
+            to: to.parse()?,
+        })
+    }
+}
+
+impl Display for RepeatPoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RepeatPoint::Packet(position) => { write!(f, "{position}") }
+            RepeatPoint::Time(time) => { write!(f, "{}s", time.as_secs_f64()) }
+        }
+    }
+}
+
+impl Display for RepeatSection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.from, self.to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use super::*;
+
+    fn datagrams(seconds: impl IntoIterator<Item = u64>) -> Vec<Datagram<'static>> {
+        seconds.into_iter().map(|secs| Datagram {
+            timestamp: Duration::from_secs(secs),
+            source: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 2000),
+            destination: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 3000),
+            payload: Cow::Borrowed(&[]),
+            frame: None,
+        }).collect()
+    }
+
+    #[test]
+    fn resolve_sections_by_position_and_time() {
+        let datagrams = datagrams(10..20);
+        let resolve = |section: &str| section.parse::<RepeatSection>().unwrap().resolve(&datagrams, true);
+
+        assert_eq!(resolve("2..4"), Some((1, 3)));
+        assert_eq!(resolve("8..100"), Some((7, 9)));
+        assert_eq!(resolve("1.5s..4s"), Some((2, 4)));
+        assert_eq!(resolve("3..2s"), Some((2, 2)));
+        assert_eq!(resolve("5..4"), None);
+        assert!("0..4".parse::<RepeatSection>().is_err());
+        assert!("2-4".parse::<RepeatSection>().is_err());
+    }
+
+    #[test]
+    fn resolve_sections_by_time_out_of_order() {
+        let datagrams = datagrams([0, 1, 4, 2, 3, 5]);
+        let resolve = |section: &str| section.parse::<RepeatSection>().unwrap().resolve(&datagrams, false);
+
+        assert_eq!(resolve("2s..3s"), Some((2, 4)));
+        assert_eq!(resolve("4s..5s"), Some((2, 5)));
+        assert_eq!(resolve("0s..1s"), Some((0, 1)));
+        assert_eq!(resolve("6s..7s"), None);
+    }
+}
@@ -0,0 +1,544 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+use log::warn;
+use packet_rehash_core::sink::{PacketSink, SinkPacket};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use pcap_files::{EnhancedPacketBlock, InterfaceDescriptionBlock, PcapFileHeader, PcapNgWriter, PcapPacketRecord, PcapWriter,
+                 SectionHeaderBlock, TimestampResolution, IP_PROTOCOL_UDP, LINKTYPE_RAW};
+
+use crate::bind::BindAddress;
+use crate::multicast::MulticastOptions;
+use crate::PlayerError;
+
+const PCAP_SNAP_LEN : u32 = 262144;
+const ENCODED_TTL : u8 = 64;
+
+/// Sends each packet as a UDP datagram, from a socket of the family of its destination bound to the source port.
+/// This is the sink of the `Player` unless another one is given.
+#[derive(Debug)]
+pub struct UdpSink {
+    sockets: Sockets,
+}
+
+/// Sends each packet as a UDP datagram to multicast groups, with its own TTL, interface and loopback.
+#[derive(Debug)]
+pub struct MulticastSink {
+    sockets: Sockets,
+}
+
+/// A UDP socket per address family, bound to the same port.
+#[derive(Debug)]
+struct Sockets {
+    ipv4: Option<UdpSocket>,
+    ipv6: Option<UdpSocket>,
+    scope_id: u32,      // the scope of link-scoped IPv6 destinations that have none
+}
+
+/// Writes the payloads of all packets to a single TCP connection, regardless of their destination.
+/// Optionally each payload is preceded by its length as a 16-bit big-endian number, to keep the packets apart.
+#[derive(Debug)]
+pub struct TcpSink {
+    stream: BufWriter<TcpStream>,
+    length_prefixed: bool,
+}
+
+/// Writes each packet as an IP/UDP packet to a .pcap file, at its captured timestamp or the time it is sent.
+pub struct PcapSink<W: Write + Send> {
+    writer: PcapWriter<W>,
+    sent_timestamps: bool,
+}
+
+/// Writes each packet as an IP/UDP packet to a .pcapng file, at its captured timestamp or the time it is sent.
+pub struct PcapNgSink<W: Write + Send> {
+    writer: PcapNgWriter<W>,
+    interface_id: u32,
+    sent_timestamps: bool,
+}
+
+/// Passes a copy of each packet to a channel, to capture replayed packets in-process.
+#[derive(Debug)]
+pub struct ChannelSink {
+    sender: Sender<ReplayedPacket>,
+}
+
+/// A packet received from a `ChannelSink`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayedPacket {
+    pub timestamp: Duration,
+    pub sent_at: Duration,
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub payload: Vec<u8>,
+}
+
+impl UdpSink {
+    /// Sends from the unspecified address of both IPv4 and IPv6, as far as the host supports IPv6.
+    /// Unicast and broadcast datagrams are sent with `ttl`, multicast datagrams according to `multicast`.
+    pub fn new(source_port: u16, ttl: u32, multicast: &MulticastOptions) -> Result<Self, PlayerError> {
+        Ok(Self { sockets: Sockets::bind(None, source_port, multicast, |socket| Self::configure(socket, ttl))? })
+    }
+
+    /// Sends from the local `address` only, so to destinations of its address family.
+    pub fn bind(address: &BindAddress, source_port: u16, ttl: u32, multicast: &MulticastOptions) -> Result<Self, PlayerError> {
+        Ok(Self { sockets: Sockets::bind(Some(address), source_port, multicast, |socket| Self::configure(socket, ttl))? })
+    }
+
+    fn configure(socket: &UdpSocket, ttl: u32) -> Result<(), PlayerError> {
+        if socket.local_addr().is_ok_and(|address| address.is_ipv6()) {
+            SockRef::from(socket).set_unicast_hops_v6(ttl)
+                .map_err(|err| PlayerError::SocketOptionError { option: "IPV6_UNICAST_HOPS", reason: err.to_string() })
+        } else {
+            socket.set_broadcast(true)
+                .map_err(|err| PlayerError::SocketOptionError { option: "SO_BROADCAST", reason: err.to_string() })?;
+            socket.set_ttl(ttl)
+                .map_err(|err| PlayerError::SocketOptionError { option: "IP_TTL", reason: err.to_string() })
+        }
+    }
+}
+
+impl PacketSink for UdpSink {
+    fn send(&mut self, packet: &SinkPacket) -> Result<(), Error> {
+        self.sockets.send_to(packet.payload, packet.destination)
+    }
+}
+
+impl MulticastSink {
+    pub fn new(source_port: u16, multicast: &MulticastOptions) -> Result<Self, PlayerError> {
+        Ok(Self { sockets: Sockets::bind(None, source_port, multicast, |_socket| Ok(()))? })
+    }
+}
+
+impl PacketSink for MulticastSink {
+    fn send(&mut self, packet: &SinkPacket) -> Result<(), Error> {
+        self.sockets.send_to(packet.payload, packet.destination)
+    }
+}
+
+impl Sockets {
+    /// Binds to `address`, or to the unspecified address of each family when `None`. Failing to bind IPv6
+    /// in the latter case only leaves the sockets unable to send to IPv6 destinations.
+    fn bind(address: Option<&BindAddress>, port: u16, multicast: &MulticastOptions,
+            configure: impl Fn(&UdpSocket) -> Result<(), PlayerError>) -> Result<Self, PlayerError> {
+        let bind_failed = |err: Error| PlayerError::BindError { port, reason: err.to_string() };
+        let open = |address: SocketAddr| {
+            let socket = Socket::new(Domain::for_address(address), Type::DGRAM, Some(Protocol::UDP)).map_err(bind_failed)?;
+            if address.is_ipv6() {
+                // Keep IPv4 to the other socket, which may be bound to the same port.
+                socket.set_only_v6(true).map_err(bind_failed)?;
+            }
+            socket.bind(&address.into()).map_err(bind_failed)?;
+            let socket = UdpSocket::from(socket);
+            configure(&socket)?;
+            multicast.apply(&socket)?;
+            Ok::<_, PlayerError>(socket)
+        };
+        let (ipv4, ipv6) = match address {
+            Some(address) => {
+                let address = address.socket_address(port, multicast.interface.as_ref()).map_err(bind_failed)?;
+                match address {
+                    SocketAddr::V4(_) => { (Some(open(address)?), None) }
+                    SocketAddr::V6(_) => { (None, Some(open(address)?)) }
+                }
+            }
+            None => {
+                let ipv4 = open(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port))?;
+                let ipv6 = open(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port))
+                    .inspect_err(|err| warn!("Cannot send to IPv6 destinations: {err}"))
+                    .ok();
+                (Some(ipv4), ipv6)
+            }
+        };
+        let scope_id = match (&ipv6, &multicast.interface) {
+            (Some(_), Some(interface)) => {
+                interface.index()
+                    .map_err(|err| PlayerError::SocketOptionError { option: "IPV6_MULTICAST_IF", reason: err.to_string() })?
+            }
+            (Some(socket), None) => {
+                match socket.local_addr() {
+                    Ok(SocketAddr::V6(address)) => { address.scope_id() }
+                    _ => { 0 }
+                }
+            }
+            _ => { 0 }
+        };
+        Ok(Self { ipv4, ipv6, scope_id })
+    }
+
+    /// Sends from the socket of the family of the destination. Link-scoped IPv6 destinations without
+    /// a scope are sent on the multicast interface, or else on the interface of the bind address, if any.
+    fn send_to(&self, payload: &[u8], destination: SocketAddr) -> Result<(), Error> {
+        let (socket, destination) = match destination {
+            SocketAddr::V4(_) => { (self.ipv4.as_ref(), destination) }
+            SocketAddr::V6(mut destination) => {
+                if destination.scope_id() == 0 && is_link_scoped(destination.ip()) {
+                    destination.set_scope_id(self.scope_id);
+                }
+                (self.ipv6.as_ref(), SocketAddr::V6(destination))
+            }
+        };
+        let socket = socket.ok_or_else(|| Error::new(ErrorKind::Unsupported,
+            format!("No socket of the address family of destination {destination}")))?;
+        socket.send_to(payload, destination).map(|_bytes_send| ())
+    }
+}
+
+/// Whether the address is only meaningful on a link: unicast link-local, or multicast of interface- or link-local scope.
+fn is_link_scoped(address: &Ipv6Addr) -> bool {
+    let first = address.segments()[0];
+    (first & 0xffc0) == 0xfe80 || (address.is_multicast() && (first & 0x000f) <= 2)
+}
+
+impl TcpSink {
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self, Error> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream: BufWriter::new(stream),
+            length_prefixed: false,
+        })
+    }
+
+    pub fn with_length_prefix(mut self) -> Self {
+        self.length_prefixed = true;
+        self
+    }
+}
+
+impl PacketSink for TcpSink {
+    fn send(&mut self, packet: &SinkPacket) -> Result<(), Error> {
+        if self.length_prefixed {
+            let length = u16::try_from(packet.payload.len())
+                .map_err(|_| Error::new(ErrorKind::InvalidInput, "The payload is too large for a length prefix"))?;
+            self.stream.write_all(&length.to_be_bytes())?;
+        }
+        self.stream.write_all(packet.payload)?;
+        self.stream.flush()
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.stream.flush()
+    }
+}
+
+impl PcapSink<BufWriter<File>> {
+    /// Creates the file, replacing any existing file.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write + Send> PcapSink<W> {
+    pub fn new(writer: W) -> Result<Self, Error> {
+        let mut header = PcapFileHeader::new(LINKTYPE_RAW, PCAP_SNAP_LEN);
+        header.ts_resolution = TimestampResolution::NANOS;
+        Ok(Self {
+            writer: PcapWriter::new(writer, &header)?,
+            sent_timestamps: false,
+        })
+    }
+
+    /// Stamps the packets with the time they are sent instead of their captured timestamp.
+    pub fn with_sent_timestamps(mut self) -> Self {
+        self.sent_timestamps = true;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write + Send> PacketSink for PcapSink<W> {
+    fn send(&mut self, packet: &SinkPacket) -> Result<(), Error> {
+        let data = encode_ip_udp(packet.source, packet.destination, packet.payload)?;
+        let timestamp = if self.sent_timestamps { packet.sent_at } else { packet.timestamp };
+        // The seconds of .pcap timestamps run out in 2106.
+        let ts_secs = u32::try_from(timestamp.as_secs()).map_err(|_| Error::new(ErrorKind::InvalidInput,
+            format!("Timestamp {}s does not fit a .pcap file", timestamp.as_secs())))?;
+        self.writer.write_packet(&PcapPacketRecord {
+            ts_secs,
+            ts_secs_fraction: timestamp.subsec_nanos(),
+            captured_packet_length: data.len() as u32,
+            original_packet_length: data.len() as u32,
+            packet_data: data,
+        })
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+}
+
+impl PcapNgSink<BufWriter<File>> {
+    /// Creates the file, replacing any existing file.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write + Send> PcapNgSink<W> {
+    pub fn new(writer: W) -> Result<Self, Error> {
+        let mut header = SectionHeaderBlock::new();
+        header.user_application = Some(format!("packet-play {}", env!("CARGO_PKG_VERSION")));
+        let mut writer = PcapNgWriter::new(writer, &header)?;
+        let mut interface = InterfaceDescriptionBlock::new(LINKTYPE_RAW as u16, PCAP_SNAP_LEN);
+        interface.ts_resolution = TimestampResolution::NANOS;
+        let interface_id = writer.write_interface(&interface)?;
+        Ok(Self {
+            writer,
+            interface_id,
+            sent_timestamps: false,
+        })
+    }
+
+    /// Stamps the packets with the time they are sent instead of their captured timestamp.
+    pub fn with_sent_timestamps(mut self) -> Self {
+        self.sent_timestamps = true;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write + Send> PacketSink for PcapNgSink<W> {
+    fn send(&mut self, packet: &SinkPacket) -> Result<(), Error> {
+        let data = encode_ip_udp(packet.source, packet.destination, packet.payload)?;
+        let timestamp = if self.sent_timestamps { packet.sent_at } else { packet.timestamp };
+        self.writer.write_enhanced_packet(&EnhancedPacketBlock::new(
+            self.interface_id, TimestampResolution::NANOS.to_ticks(timestamp), data))
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+}
+
+/// Creates a .pcap or .pcapng file, by its extension, that stamps the packets with the time they are sent.
+pub fn file_sink(path: impl AsRef<Path>) -> Result<Box<dyn PacketSink>, Error> {
+    let path = path.as_ref();
+    match path.extension().and_then(OsStr::to_str) {
+        Some("pcap") => { Ok(Box::new(PcapSink::create(path)?.with_sent_timestamps())) }
+        Some("pcapng") => { Ok(Box::new(PcapNgSink::create(path)?.with_sent_timestamps())) }
+        _ => { Err(Error::new(ErrorKind::InvalidInput, "The output file must have the extension .pcap or .pcapng")) }
+    }
+}
+
+impl ChannelSink {
+    pub fn new() -> (Self, Receiver<ReplayedPacket>) {
+        let (sender, receiver) = channel();
+        (Self { sender }, receiver)
+    }
+}
+
+impl PacketSink for ChannelSink {
+    fn send(&mut self, packet: &SinkPacket) -> Result<(), Error> {
+        self.sender.send(ReplayedPacket {
+            timestamp: packet.timestamp,
+            sent_at: packet.sent_at,
+            source: packet.source,
+            destination: packet.destination,
+            payload: packet.payload.to_vec(),
+        }).map_err(|_| Error::new(ErrorKind::BrokenPipe, "The receiving end of the channel is closed"))
+    }
+}
+
+/// Wraps the payload in UDP and IP headers, which must be of the same IP version.
+pub(crate) fn encode_ip_udp(source: SocketAddr, destination: SocketAddr, payload: &[u8]) -> Result<Vec<u8>, Error> {
+    let too_large = || Error::new(ErrorKind::InvalidInput, format!("A payload of {} bytes does not fit a UDP datagram", payload.len()));
+    let udp_length = u16::try_from(payload.len() + 8).map_err(|_| too_large())?;
+
+    let mut udp = Vec::with_capacity(udp_length as usize);
+    udp.extend_from_slice(&source.port().to_be_bytes());
+    udp.extend_from_slice(&destination.port().to_be_bytes());
+    udp.extend_from_slice(&udp_length.to_be_bytes());
+    udp.extend_from_slice(&[0, 0]); // checksum
+    udp.extend_from_slice(payload);
+
+    let mut packet = match (source.ip(), destination.ip()) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            let total_length = u16::try_from(udp.len() + 20).map_err(|_| too_large())?;
+            let mut header = vec![0x45, 0];
+            header.extend_from_slice(&total_length.to_be_bytes());
+            header.extend_from_slice(&[0, 0, 0x40, 0, ENCODED_TTL, IP_PROTOCOL_UDP, 0, 0]); // id, DF, checksum
+            header.extend_from_slice(&source.octets());
+            header.extend_from_slice(&destination.octets());
+            let header_checksum = checksum(&header);
+            header[10..12].copy_from_slice(&header_checksum.to_be_bytes());
+            header
+        }
+        (IpAddr::V6(source), IpAddr::V6(destination)) => {
+            let mut header = vec![0x60, 0, 0, 0];
+            header.extend_from_slice(&udp_length.to_be_bytes());
+            header.extend_from_slice(&[IP_PROTOCOL_UDP, ENCODED_TTL]);
+            header.extend_from_slice(&source.octets());
+            header.extend_from_slice(&destination.octets());
+            header
+        }
+        _ => {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                format!("Cannot encode a packet from {source} to {destination}, of different IP versions")));
+        }
+    };
+
+    let octets = |address: IpAddr| match address {
+        IpAddr::V4(address) => { address.octets().to_vec() }
+        IpAddr::V6(address) => { address.octets().to_vec() }
+    };
+    let udp_checksum = udp_checksum(&octets(source.ip()), &octets(destination.ip()), &udp);
+    udp[6..8].copy_from_slice(&udp_checksum.to_be_bytes());
+    packet.extend_from_slice(&udp);
+    Ok(packet)
+}
+
+/// The checksum of a UDP datagram with its checksum field zeroed, between the IPv4 or IPv6 addresses given as octets.
+pub(crate) fn udp_checksum(source: &[u8], destination: &[u8], udp: &[u8]) -> u16 {
+    let mut pseudo_header = [source, destination].concat();
+    if source.len() == 4 {
+        pseudo_header.extend_from_slice(&[0, IP_PROTOCOL_UDP]);
+        pseudo_header.extend_from_slice(&(udp.len() as u16).to_be_bytes());
+    } else {
+        pseudo_header.extend_from_slice(&(udp.len() as u32).to_be_bytes());
+        pseudo_header.extend_from_slice(&[0, 0, 0, IP_PROTOCOL_UDP]);
+    }
+    pseudo_header.extend_from_slice(udp);
+    // A computed checksum of zero is sent as all ones, as zero means no checksum.
+    match checksum(&pseudo_header) {
+        0 => { 0xFFFF }
+        sum => { sum }
+    }
+}
+
+/// The internet checksum: the one's complement of the one's complement sum of all 16-bit words.
+pub(crate) fn checksum(data: &[u8]) -> u16 {
+    let mut sum = data.chunks(2)
+        .map(|word| u32::from(word[0]) << 8 | u32::from(*word.get(1).unwrap_or(&0)))
+        .sum::<u32>();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use pcap_files::{decode_packet, Pcap};
+    use crate::{Command, DestinationMode, OutputStart, PlaybackSpeed, Player, Recording};
+    use super::*;
+
+    #[test]
+    fn write_packets_to_pcap() {
+        let mut sink = PcapSink::new(Vec::new()).unwrap();
+        let source: SocketAddr = "10.0.0.1:3000".parse().unwrap();
+        for (destination, payload) in [("239.1.2.3:3001", &b"first"[..]), ("10.0.0.2:3002", &b"second, odd"[..])] {
+            sink.send(&SinkPacket {
+                timestamp: Duration::new(1_700_000_000, 123_456_789),
+                sent_at: Duration::ZERO,
+                source,
+                destination: destination.parse().unwrap(),
+                payload,
+                captured: None,
+            }).unwrap();
+        }
+        sink.flush().unwrap();
+
+        let pcap = Pcap::try_from(sink.into_inner().as_slice()).unwrap();
+        assert_eq!(pcap.header.timestamp(pcap.packets[0].ts_secs, pcap.packets[0].ts_secs_fraction),
+                   Duration::new(1_700_000_000, 123_456_789));
+        let packet = decode_packet(LINKTYPE_RAW, &pcap.packets[1].packet_data).unwrap();
+        let udp = packet.udp.unwrap();
+        assert_eq!(packet.ip.source(), source.ip());
+        assert_eq!((udp.source_port, udp.destination_port), (3000, 3002));
+        assert_eq!(udp.payload, b"second, odd");
+        assert_eq!(checksum(&pcap.packets[1].packet_data[..20]), 0);
+
+        let mut sink = PcapSink::new(Vec::new()).unwrap().with_sent_timestamps();
+        let after_2106 = sink.send(&SinkPacket {
+            timestamp: Duration::ZERO,
+            sent_at: Duration::from_secs(u32::MAX as u64 + 1),
+            source,
+            destination: source,
+            payload: b"late",
+            captured: None,
+        });
+        assert_eq!(after_2106.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn send_to_either_address_family() {
+        let mut sink = UdpSink::new(0, 1, &MulticastOptions::default()).unwrap();
+        let mut ipv4_only = UdpSink::bind(&IpAddr::V4(Ipv4Addr::LOCALHOST).into(), 0, 1, &MulticastOptions::default()).unwrap();
+        for address in [IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)] {
+            let receiver = UdpSocket::bind(SocketAddr::new(address, 0)).unwrap();
+            receiver.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+            let packet = SinkPacket {
+                timestamp: Duration::ZERO,
+                sent_at: Duration::ZERO,
+                source: "10.0.0.1:3000".parse().unwrap(),
+                destination: receiver.local_addr().unwrap(),
+                payload: b"dual-stack",
+                captured: None,
+            };
+            sink.send(&packet).unwrap();
+            let mut buffer = [0; 16];
+            let (length, from) = receiver.recv_from(&mut buffer).unwrap();
+            assert_eq!(&buffer[..length], b"dual-stack");
+            assert_eq!(from.is_ipv6(), address.is_ipv6());
+            assert_eq!(ipv4_only.send(&packet).is_ok(), address.is_ipv4());
+        }
+        assert!(is_link_scoped(&"ff02::1".parse().unwrap()));
+        assert!(is_link_scoped(&"fe80::1".parse().unwrap()));
+        assert!(!is_link_scoped(&"ff05::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn capture_replayed_packets_in_process() {
+        let path = std::env::temp_dir().join(format!("packet-play-sinks-{}.pcap", std::process::id()));
+        let mut recording_sink = PcapSink::create(&path).unwrap();
+        for i in 0..3u8 {
+            recording_sink.send(&SinkPacket {
+                timestamp: Duration::from_millis(1_000 + i as u64),
+                sent_at: Duration::ZERO,
+                source: "10.0.0.1:3000".parse().unwrap(),
+                destination: SocketAddr::new(Ipv4Addr::new(239, 1, 2, 3).into(), 4000 + i as u16),
+                payload: &[i; 10],
+                captured: None,
+            }).unwrap();
+        }
+        drop(recording_sink);
+        let recording = Recording::try_from(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let (sink, replayed) = ChannelSink::new();
+        let (cmd_sender, cmd_receiver) = mpsc::channel();
+        let (event_sender, _event_receiver) = mpsc::channel();
+        let player = Player::builder()
+            .recording(recording)
+            .destination("127.0.0.1:5000".parse().unwrap())
+            .destination_mode(DestinationMode::OriginalPort)
+            .speed(PlaybackSpeed::Multiplier(2.0))
+            .sink(sink)
+            .simulate(OutputStart::At(Duration::from_secs(100)))
+            .cmd_rx(cmd_receiver)
+            .event_tx(event_sender)
+            .build().unwrap();
+        cmd_sender.send(Command::Play).unwrap();
+        let packets: Vec<ReplayedPacket> = replayed.iter().take(3).collect();
+        cmd_sender.send(Command::Quit).unwrap();
+        player.join().unwrap().unwrap();
+
+        assert_eq!(packets[2].destination, "127.0.0.1:4002".parse().unwrap());
+        assert_eq!(packets[2].source, "10.0.0.1:3000".parse().unwrap());
+        assert_eq!(packets[2].payload, vec![2; 10]);
+        assert_eq!(packets[1].timestamp, Duration::from_millis(1_001));
+        assert_eq!(packets[2].sent_at, Duration::from_secs(100) + Duration::from_millis(1));
+    }
+}
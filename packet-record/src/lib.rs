@@ -1,3 +1,7 @@
+/// Capture filters are written in the pcap-filter syntax of tcpdump and evaluated in Rust, so recording does
+/// not depend on libpcap. A captured packet is recorded when `matches` accepts its link type and data.
+pub use pcap_files::BpfFilter as CaptureFilter;
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -1,2 +1,2 @@
+pub mod sink;
 pub mod utils;
-
@@ -0,0 +1,43 @@
+use std::io::Error;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// A packet handed to a `PacketSink` for replay.
+#[derive(Copy, Clone, Debug)]
+pub struct SinkPacket<'a> {
+    pub timestamp: Duration,        // the captured timestamp, since the epoch
+    pub sent_at: Duration,          // when the packet is sent, since the epoch; simulated when replaying to a file
+    pub source: SocketAddr,         // the captured source address and port
+    pub destination: SocketAddr,    // where to replay the packet to
+    pub payload: &'a [u8],
+    pub captured: Option<CapturedFrame<'a>>,    // the captured frame, unless the datagram was reassembled from fragments
+}
+
+/// A frame as captured, including its link-layer headers.
+#[derive(Copy, Clone, Debug)]
+pub struct CapturedFrame<'a> {
+    pub link_type: u32,
+    pub data: &'a [u8],
+}
+
+/// Where replayed packets go, such as a socket or a file.
+/// Sinks are moved to the thread that replays, so they must be `Send`.
+pub trait PacketSink: Send {
+    /// Sends or writes a single packet.
+    fn send(&mut self, packet: &SinkPacket) -> Result<(), Error>;
+
+    /// Writes out any buffered packets, when playback finishes or stops.
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<S: PacketSink + ?Sized> PacketSink for Box<S> {
+    fn send(&mut self, packet: &SinkPacket) -> Result<(), Error> {
+        (**self).send(packet)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        (**self).flush()
+    }
+}
@@ -1,3 +1,73 @@
-pub const ETHERNET_HEADER_LENGTH_BYTES : u16 = 13;
+pub const ETHERNET_HEADER_LENGTH_BYTES : u16 = 14;
 pub const IP_HEADER_LENGTH_BYTES : u16 = 20;
 pub const UDP_HEADER_LENGTH_BYTES : u16 = 8;
+
+pub const LINKTYPE_NULL : u32 = 0;           // BSD loopback, protocol family in host byte order
+pub const LINKTYPE_ETHERNET : u32 = 1;
+pub const LINKTYPE_RAW : u32 = 101;          // raw IPv4 or IPv6
+pub const LINKTYPE_LOOP : u32 = 108;         // OpenBSD loopback, protocol family in network byte order
+pub const LINKTYPE_LINUX_SLL : u32 = 113;    // Linux cooked capture, as by `tcpdump -i any`
+pub const LINKTYPE_IPV4 : u32 = 228;
+pub const LINKTYPE_IPV6 : u32 = 229;
+pub const LINKTYPE_LINUX_SLL2 : u32 = 276;   // Linux cooked capture v2
+
+pub const PCAP_FILE_HEADER_LENGTH : usize = 24;
+pub const PCAP_RECORD_HEADER_LENGTH : usize = 16;
+
+// EtherTypes
+pub(crate) const ETHERTYPE_IPV4 : u16 = 0x0800;
+pub(crate) const ETHERTYPE_IPV6 : u16 = 0x86DD;
+pub(crate) const ETHERTYPE_VLAN : u16 = 0x8100;           // 802.1Q customer tag
+pub(crate) const ETHERTYPE_QINQ : u16 = 0x88A8;           // 802.1ad service tag
+pub(crate) const ETHERTYPE_QINQ_LEGACY : u16 = 0x9100;    // pre-standard service tag
+
+// Protocol families of the NULL and LOOP link types; the value for IPv6 differs per operating system
+pub(crate) const AF_INET : u32 = 2;
+pub(crate) const AF_INET6_LINUX : u32 = 10;
+pub(crate) const AF_INET6_BSD : u32 = 24;
+pub(crate) const AF_INET6_FREEBSD : u32 = 28;
+pub(crate) const AF_INET6_DARWIN : u32 = 30;
+
+// IP protocol numbers, including the IPv6 extension headers
+pub(crate) const IP_PROTOCOL_HOPOPT : u8 = 0;
+pub(crate) const IP_PROTOCOL_ICMP : u8 = 1;
+pub(crate) const IP_PROTOCOL_TCP : u8 = 6;
+pub const IP_PROTOCOL_UDP : u8 = 17;
+pub(crate) const IP_PROTOCOL_IPV6_ROUTE : u8 = 43;
+pub(crate) const IP_PROTOCOL_IPV6_FRAG : u8 = 44;
+pub(crate) const IP_PROTOCOL_AH : u8 = 51;
+pub(crate) const IP_PROTOCOL_ICMPV6 : u8 = 58;
+pub(crate) const IP_PROTOCOL_IPV6_OPTS : u8 = 60;
+pub(crate) const IP_PROTOCOL_MOBILITY : u8 = 135;
+pub(crate) const IP_PROTOCOL_HIP : u8 = 139;
+pub(crate) const IP_PROTOCOL_SHIM6 : u8 = 140;
+
+// pcapng block types
+pub(crate) const SECTION_HEADER_BLOCK : u32 = 0x0A0D0D0A;
+pub(crate) const INTERFACE_DESCRIPTION_BLOCK : u32 = 0x00000001;
+pub(crate) const SIMPLE_PACKET_BLOCK : u32 = 0x00000003;
+pub(crate) const NAME_RESOLUTION_BLOCK : u32 = 0x00000004;
+pub(crate) const INTERFACE_STATISTICS_BLOCK : u32 = 0x00000005;
+pub(crate) const ENHANCED_PACKET_BLOCK : u32 = 0x00000006;
+pub(crate) const BYTE_ORDER_MAGIC : u32 = 0x1A2B3C4D;
+pub(crate) const KNOWN_BLOCK_TYPES : [u32; 5] = [INTERFACE_DESCRIPTION_BLOCK, SIMPLE_PACKET_BLOCK, NAME_RESOLUTION_BLOCK,
+    INTERFACE_STATISTICS_BLOCK, ENHANCED_PACKET_BLOCK];
+
+// pcapng option codes, generic and per block type
+pub(crate) const OPT_ENDOFOPT : u16 = 0;
+pub(crate) const OPT_COMMENT : u16 = 1;
+pub(crate) const SHB_HARDWARE : u16 = 2;
+pub(crate) const SHB_OS : u16 = 3;
+pub(crate) const SHB_USERAPPL : u16 = 4;
+pub(crate) const IF_NAME : u16 = 2;
+pub(crate) const IF_DESCRIPTION : u16 = 3;
+pub(crate) const IF_TSRESOL : u16 = 9;
+pub(crate) const IF_TSOFFSET : u16 = 14;
+pub(crate) const EPB_FLAGS : u16 = 2;
+pub(crate) const ISB_IFRECV : u16 = 4;
+pub(crate) const ISB_IFDROP : u16 = 5;
+
+// pcapng name resolution record types
+pub(crate) const NRB_RECORD_END : u16 = 0;
+pub(crate) const NRB_RECORD_IPV4 : u16 = 1;
+pub(crate) const NRB_RECORD_IPV6 : u16 = 2;
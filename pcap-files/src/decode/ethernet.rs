@@ -0,0 +1,58 @@
+use nom::bytes::complete::take;
+use nom::IResult;
+use nom::number::complete::be_u16;
+use crate::constants::*;
+use crate::decode::{truncated, DecodeError};
+
+/// An Ethernet II header, including any 802.1Q/802.1ad VLAN tags.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EthernetHeader {
+    pub destination: [u8; 6],
+    pub source: [u8; 6],
+    pub vlan_tags: Vec<VlanTag>,    // outermost tag first
+    pub ether_type: u16,            // the EtherType of the payload, following the VLAN tags
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VlanTag {
+    pub tpid: u16,              // tag protocol identifier, the EtherType announcing the tag
+    pub priority: u8,
+    pub drop_eligible: bool,
+    pub vlan_id: u16,
+}
+
+/// Splits an Ethernet frame into its header and payload.
+pub(crate) fn ethernet_frame(data: &[u8]) -> Result<(EthernetHeader, &[u8]), DecodeError> {
+    ethernet_header(data).map(|(payload, header)| (header, payload)).map_err(truncated("Ethernet"))
+}
+
+fn ethernet_header(input: &[u8]) -> IResult<&[u8], EthernetHeader> {
+    let (input, destination) = take(6usize)(input)?;
+    let (input, source) = take(6usize)(input)?;
+    let (mut input, mut ether_type) = be_u16(input)?;
+
+    let mut vlan_tags = Vec::new();
+    while is_vlan_tpid(ether_type) {
+        let (rest, tci) = be_u16(input)?;
+        let (rest, inner_ether_type) = be_u16(rest)?;
+        vlan_tags.push(VlanTag {
+            tpid: ether_type,
+            priority: (tci >> 13) as u8,
+            drop_eligible: tci & 0x1000 != 0,
+            vlan_id: tci & 0x0FFF,
+        });
+        ether_type = inner_ether_type;
+        input = rest;
+    }
+
+    Ok((input, EthernetHeader {
+        destination: destination.try_into().expect("Took 6 bytes."),
+        source: source.try_into().expect("Took 6 bytes."),
+        vlan_tags,
+        ether_type,
+    }))
+}
+
+fn is_vlan_tpid(ether_type: u16) -> bool {
+    matches!(ether_type, ETHERTYPE_VLAN | ETHERTYPE_QINQ | ETHERTYPE_QINQ_LEGACY)
+}
@@ -0,0 +1,297 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use nom::bytes::complete::take;
+use nom::IResult;
+use nom::number::complete::{be_u16, be_u32, be_u8};
+use crate::constants::*;
+use crate::decode::{truncated, DecodeError};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IpPacket<'a> {
+    V4(Ipv4Packet<'a>),
+    V6(Ipv6Packet<'a>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ipv4Packet<'a> {
+    pub dscp: u8,
+    pub ecn: u8,
+    pub total_length: u16,
+    pub identification: u16,
+    pub dont_fragment: bool,
+    pub more_fragments: bool,
+    pub fragment_offset: u16,   // in bytes
+    pub ttl: u8,
+    pub protocol: u8,
+    pub source: Ipv4Addr,
+    pub destination: Ipv4Addr,
+    pub options: &'a [u8],
+    pub payload: &'a [u8],      // excluding any link-layer padding
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ipv6Packet<'a> {
+    pub traffic_class: u8,
+    pub flow_label: u32,
+    pub payload_length: u16,
+    pub hop_limit: u8,
+    pub source: Ipv6Addr,
+    pub destination: Ipv6Addr,
+    pub next_header: u8,                    // the upper-layer protocol, following any extension headers
+    pub fragment: Option<IpFragment>,
+    pub payload: &'a [u8],                  // the upper-layer payload, after the extension headers
+}
+
+/// The fragmentation fields of an IPv4 header, or the contents of an IPv6 Fragment extension header.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IpFragment {
+    pub identification: u32,
+    pub fragment_offset: u16,   // in bytes
+    pub more_fragments: bool,
+}
+
+impl IpFragment {
+    /// Whether this holds only a part of the payload, rather than being the single fragment of a complete packet.
+    pub fn is_partial(&self) -> bool {
+        self.more_fragments || self.fragment_offset != 0
+    }
+}
+
+impl<'a> IpPacket<'a> {
+    pub fn source(&self) -> IpAddr {
+        match self {
+            IpPacket::V4(packet) => { IpAddr::V4(packet.source) }
+            IpPacket::V6(packet) => { IpAddr::V6(packet.source) }
+        }
+    }
+
+    pub fn destination(&self) -> IpAddr {
+        match self {
+            IpPacket::V4(packet) => { IpAddr::V4(packet.destination) }
+            IpPacket::V6(packet) => { IpAddr::V6(packet.destination) }
+        }
+    }
+
+    /// The upper-layer protocol number, such as 17 for UDP.
+    pub fn protocol(&self) -> u8 {
+        match self {
+            IpPacket::V4(packet) => { packet.protocol }
+            IpPacket::V6(packet) => { packet.next_header }
+        }
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        match self {
+            IpPacket::V4(packet) => { packet.payload }
+            IpPacket::V6(packet) => { packet.payload }
+        }
+    }
+
+    /// The fragmentation fields, if the packet holds only a part of the upper-layer payload.
+    pub fn fragment(&self) -> Option<IpFragment> {
+        let fragment = match self {
+            IpPacket::V4(packet) => {
+                Some(IpFragment {
+                    identification: packet.identification as u32,
+                    fragment_offset: packet.fragment_offset,
+                    more_fragments: packet.more_fragments,
+                })
+            }
+            IpPacket::V6(packet) => { packet.fragment }
+        };
+        fragment.filter(IpFragment::is_partial)
+    }
+
+    pub fn is_fragment(&self) -> bool {
+        self.fragment().is_some()
+    }
+}
+
+/// Decodes an IP packet carried with the given EtherType.
+pub(crate) fn ip_packet(ether_type: u16, data: &[u8]) -> Result<IpPacket<'_>, DecodeError> {
+    match ether_type {
+        ETHERTYPE_IPV4 => { ipv4_packet(data).map(IpPacket::V4) }
+        ETHERTYPE_IPV6 => { ipv6_packet(data).map(IpPacket::V6) }
+        _ => { Err(DecodeError::UnsupportedEtherType(ether_type)) }
+    }
+}
+
+fn ipv4_packet(data: &[u8]) -> Result<Ipv4Packet<'_>, DecodeError> {
+    let (_, (header, header_length)) = ipv4_header(data).map_err(truncated("IPv4"))?;
+    if header.version != 4 || header_length < IP_HEADER_LENGTH_BYTES as usize {
+        return Err(DecodeError::Invalid("IPv4"));
+    }
+    // A total length of 0 is seen in captures of packets offloaded for segmentation, which span the whole frame.
+    let end = match header.total_length as usize {
+        0 => { data.len() }
+        total_length if total_length < header_length => { return Err(DecodeError::Invalid("IPv4")); }
+        total_length => { total_length.min(data.len()) }
+    };
+
+    Ok(Ipv4Packet {
+        dscp: header.tos >> 2,
+        ecn: header.tos & 0x03,
+        total_length: header.total_length,
+        identification: header.identification,
+        dont_fragment: header.flags_and_offset & 0x4000 != 0,
+        more_fragments: header.flags_and_offset & 0x2000 != 0,
+        fragment_offset: (header.flags_and_offset & 0x1FFF) << 3,
+        ttl: header.ttl,
+        protocol: header.protocol,
+        source: header.source,
+        destination: header.destination,
+        options: &data[IP_HEADER_LENGTH_BYTES as usize..header_length],
+        payload: &data[header_length..end],
+    })
+}
+
+struct Ipv4Header {
+    version: u8,
+    tos: u8,
+    total_length: u16,
+    identification: u16,
+    flags_and_offset: u16,
+    ttl: u8,
+    protocol: u8,
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+}
+
+/// Parses the fixed part of the IPv4 header and checks that the options are present, returning the header length.
+fn ipv4_header(input: &[u8]) -> IResult<&[u8], (Ipv4Header, usize)> {
+    let (rest, version_ihl) = be_u8(input)?;
+    let (rest, tos) = be_u8(rest)?;
+    let (rest, total_length) = be_u16(rest)?;
+    let (rest, identification) = be_u16(rest)?;
+    let (rest, flags_and_offset) = be_u16(rest)?;
+    let (rest, ttl) = be_u8(rest)?;
+    let (rest, protocol) = be_u8(rest)?;
+    let (rest, _checksum) = be_u16(rest)?;
+    let (rest, source) = be_u32(rest)?;
+    let (rest, destination) = be_u32(rest)?;
+    let header_length = (version_ihl & 0x0F) as usize * 4;
+    let (rest, _options) = take(header_length.saturating_sub(IP_HEADER_LENGTH_BYTES as usize))(rest)?;
+
+    Ok((rest, (Ipv4Header {
+        version: version_ihl >> 4,
+        tos,
+        total_length,
+        identification,
+        flags_and_offset,
+        ttl,
+        protocol,
+        source: Ipv4Addr::from(source),
+        destination: Ipv4Addr::from(destination),
+    }, header_length)))
+}
+
+fn ipv6_packet(data: &[u8]) -> Result<Ipv6Packet<'_>, DecodeError> {
+    let (rest, header) = ipv6_header(data).map_err(truncated("IPv6"))?;
+    if header.version != 6 {
+        return Err(DecodeError::Invalid("IPv6"));
+    }
+    // A payload length of 0 is used by jumbograms, and by packets offloaded for segmentation.
+    let rest = match header.payload_length as usize {
+        0 => { rest }
+        payload_length => { &rest[..payload_length.min(rest.len())] }
+    };
+
+    let (next_header, fragment, payload) = extension_headers(header.next_header, rest)?;
+
+    Ok(Ipv6Packet {
+        traffic_class: ((header.version_class_label >> 20) & 0xFF) as u8,
+        flow_label: header.version_class_label & 0x000FFFFF,
+        payload_length: header.payload_length,
+        hop_limit: header.hop_limit,
+        source: header.source,
+        destination: header.destination,
+        next_header,
+        fragment,
+        payload,
+    })
+}
+
+struct Ipv6Header {
+    version: u8,
+    version_class_label: u32,
+    payload_length: u16,
+    next_header: u8,
+    hop_limit: u8,
+    source: Ipv6Addr,
+    destination: Ipv6Addr,
+}
+
+fn ipv6_header(input: &[u8]) -> IResult<&[u8], Ipv6Header> {
+    let (input, version_class_label) = be_u32(input)?;
+    let (input, payload_length) = be_u16(input)?;
+    let (input, next_header) = be_u8(input)?;
+    let (input, hop_limit) = be_u8(input)?;
+    let (input, source) = take(16usize)(input)?;
+    let (input, destination) = take(16usize)(input)?;
+
+    Ok((input, Ipv6Header {
+        version: (version_class_label >> 28) as u8,
+        version_class_label,
+        payload_length,
+        next_header,
+        hop_limit,
+        source: Ipv6Addr::from(<[u8; 16]>::try_from(source).expect("Took 16 bytes.")),
+        destination: Ipv6Addr::from(<[u8; 16]>::try_from(destination).expect("Took 16 bytes.")),
+    }))
+}
+
+/// Skips the extension headers starting with `next_header`, returning the upper-layer protocol, the contents
+/// of any Fragment header, and the upper-layer payload. Parsing stops after the Fragment header of a partial
+/// packet, as the headers following it are only complete once the fragments are reassembled.
+pub(crate) fn extension_headers(mut next_header: u8, mut payload: &[u8]) -> Result<(u8, Option<IpFragment>, &[u8]), DecodeError> {
+    let mut fragment = None;
+    while is_extension_header(next_header) {
+        let (rest, (following_header, extension_fragment)) = extension_header(next_header)(payload)
+            .map_err(truncated("IPv6 extension"))?;
+        next_header = following_header;
+        payload = rest;
+        if let Some(extension_fragment) = extension_fragment {
+            fragment = Some(extension_fragment);
+            if extension_fragment.is_partial() {
+                break;
+            }
+        }
+    }
+    Ok((next_header, fragment, payload))
+}
+
+/// The extension headers that can be skipped to reach the upper-layer protocol. An Encapsulating Security
+/// Payload is not one of them, as everything after its header is encrypted.
+fn is_extension_header(next_header: u8) -> bool {
+    matches!(next_header, IP_PROTOCOL_HOPOPT | IP_PROTOCOL_IPV6_ROUTE | IP_PROTOCOL_IPV6_FRAG | IP_PROTOCOL_AH
+        | IP_PROTOCOL_IPV6_OPTS | IP_PROTOCOL_MOBILITY | IP_PROTOCOL_HIP | IP_PROTOCOL_SHIM6)
+}
+
+/// Parses an extension header of type `header_type`, returning the type of the next header,
+/// and the fragment details for a Fragment header.
+fn extension_header(header_type: u8) -> impl Fn(&[u8]) -> IResult<&[u8], (u8, Option<IpFragment>)> {
+    move |input| {
+        let (rest, next_header) = be_u8(input)?;
+        match header_type {
+            IP_PROTOCOL_IPV6_FRAG => {
+                let (rest, _reserved) = be_u8(rest)?;
+                let (rest, offset_and_flags) = be_u16(rest)?;
+                let (rest, identification) = be_u32(rest)?;
+                Ok((rest, (next_header, Some(IpFragment {
+                    identification,
+                    fragment_offset: offset_and_flags & 0xFFF8,
+                    more_fragments: offset_and_flags & 0x0001 != 0,
+                }))))
+            }
+            _ => {
+                let (_, length) = be_u8(rest)?;
+                // The Authentication Header length counts 4-octet units minus 2, all others 8-octet units minus 1.
+                let length = match header_type {
+                    IP_PROTOCOL_AH => { (length as usize + 2) * 4 }
+                    _ => { (length as usize + 1) * 8 }
+                };
+                let (rest, _header) = take(length)(input)?;
+                Ok((rest, (next_header, None)))
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use nom::bytes::complete::take;
+use nom::IResult;
+use nom::number::complete::{be_u16, be_u32, be_u8, le_u32};
+use crate::constants::*;
+use crate::decode::{truncated, DecodeError};
+
+/// The pseudo link-layer header of a Linux cooked capture (`LINKTYPE_LINUX_SLL` and `LINKTYPE_LINUX_SLL2`),
+/// as captured on the `any` device.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinuxCookedHeader {
+    pub packet_type: u16,               // 0 sent to us, 1 broadcast, 2 multicast, 3 to another host, 4 sent by us
+    pub arphrd_type: u16,               // the Linux ARPHRD_ device type
+    pub address: Vec<u8>,               // link-layer source address, up to 8 bytes
+    pub protocol: u16,                  // the EtherType of the payload, for the usual device types
+    pub interface_index: Option<u32>,   // only in v2 headers
+}
+
+pub(crate) fn linux_sll(data: &[u8]) -> Result<(LinuxCookedHeader, &[u8]), DecodeError> {
+    linux_sll_header(data).map(|(payload, header)| (header, payload)).map_err(truncated("Linux cooked"))
+}
+
+pub(crate) fn linux_sll2(data: &[u8]) -> Result<(LinuxCookedHeader, &[u8]), DecodeError> {
+    linux_sll2_header(data).map(|(payload, header)| (header, payload)).map_err(truncated("Linux cooked v2"))
+}
+
+fn linux_sll_header(input: &[u8]) -> IResult<&[u8], LinuxCookedHeader> {
+    let (input, packet_type) = be_u16(input)?;
+    let (input, arphrd_type) = be_u16(input)?;
+    let (input, address_length) = be_u16(input)?;
+    let (input, address) = take(8usize)(input)?;
+    let (input, protocol) = be_u16(input)?;
+
+    Ok((input, LinuxCookedHeader {
+        packet_type,
+        arphrd_type,
+        address: address[..(address_length as usize).min(8)].to_vec(),
+        protocol,
+        interface_index: None,
+    }))
+}
+
+fn linux_sll2_header(input: &[u8]) -> IResult<&[u8], LinuxCookedHeader> {
+    let (input, protocol) = be_u16(input)?;
+    let (input, _reserved) = be_u16(input)?;
+    let (input, interface_index) = be_u32(input)?;
+    let (input, arphrd_type) = be_u16(input)?;
+    let (input, packet_type) = be_u8(input)?;
+    let (input, address_length) = be_u8(input)?;
+    let (input, address) = take(8usize)(input)?;
+
+    Ok((input, LinuxCookedHeader {
+        packet_type: packet_type as u16,
+        arphrd_type,
+        address: address[..(address_length as usize).min(8)].to_vec(),
+        protocol,
+        interface_index: Some(interface_index),
+    }))
+}
+
+/// Splits a loopback packet into the protocol family and the payload. The family is in network byte order
+/// for `LINKTYPE_LOOP`, but in the byte order of the capturing host for `LINKTYPE_NULL`, so for the latter
+/// both byte orders are accepted; families are small numbers, which a swapped value is not.
+pub(crate) fn loopback(link_type: u32, data: &[u8]) -> Result<(u32, &[u8]), DecodeError> {
+    let (payload, family) = match link_type {
+        LINKTYPE_LOOP => { be_u32(data) }
+        _ => { le_u32(data) }
+    }.map_err(truncated("loopback"))?;
+    let family = if family > 0xFFFF { family.swap_bytes() } else { family };
+    Ok((family, payload))
+}
+
+/// The EtherType of the payload of a loopback packet with the given protocol family.
+pub(crate) fn loopback_ether_type(family: u32) -> Result<u16, DecodeError> {
+    match family {
+        AF_INET => { Ok(ETHERTYPE_IPV4) }
+        AF_INET6_LINUX | AF_INET6_BSD | AF_INET6_FREEBSD | AF_INET6_DARWIN => { Ok(ETHERTYPE_IPV6) }
+        _ => { Err(DecodeError::UnsupportedAddressFamily(family)) }
+    }
+}
+
+/// The EtherType of a raw IP packet, from the version in its first byte.
+pub(crate) fn raw_ip_ether_type(data: &[u8]) -> Result<u16, DecodeError> {
+    match data.first().map(|byte| byte >> 4) {
+        Some(4) => { Ok(ETHERTYPE_IPV4) }
+        Some(6) => { Ok(ETHERTYPE_IPV6) }
+        Some(_) => { Err(DecodeError::Invalid("IP")) }
+        None => { Err(DecodeError::Truncated("IP")) }
+    }
+}
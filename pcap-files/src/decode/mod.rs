@@ -0,0 +1,181 @@
+pub(crate) mod ethernet;
+pub(crate) mod ip;
+pub(crate) mod link;
+pub(crate) mod reassembly;
+pub(crate) mod udp;
+
+use thiserror::Error;
+use crate::constants::*;
+use ethernet::{ethernet_frame, EthernetHeader};
+use ip::{ip_packet, IpPacket};
+use link::{linux_sll, linux_sll2, loopback, loopback_ether_type, raw_ip_ether_type, LinuxCookedHeader};
+use udp::{udp_datagram, UdpDatagram};
+
+/// A captured packet decoded from the link layer up to UDP.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedPacket<'a> {
+    pub link: LinkHeader,
+    pub ip: IpPacket<'a>,
+    pub udp: Option<UdpDatagram<'a>>,    // None for other protocols, and for IP fragments
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LinkHeader {
+    Ethernet(EthernetHeader),
+    LinuxSll(LinuxCookedHeader),
+    LinuxSll2(LinuxCookedHeader),
+    Loopback { family: u32 },   // the protocol family of LINKTYPE_NULL and LINKTYPE_LOOP
+    Raw,                        // no link-layer header
+}
+
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum DecodeError {
+    #[error("unsupported link type {0}")]
+    UnsupportedLinkType(u32),
+    #[error("unsupported EtherType {0:#06X}")]
+    UnsupportedEtherType(u16),
+    #[error("unsupported protocol family {0}")]
+    UnsupportedAddressFamily(u32),
+    #[error("truncated {0} header")]
+    Truncated(&'static str),
+    #[error("invalid {0} header")]
+    Invalid(&'static str),
+}
+
+/// Decodes the packet data of a capture with the given link type (the `link_type` of a .pcap header
+/// or a pcapng interface).
+pub fn decode_packet(link_type: u32, data: &[u8]) -> Result<DecodedPacket<'_>, DecodeError> {
+    let (link, ether_type, payload) = link_layer(link_type, data)?;
+    let ip = ip_packet(ether_type, payload)?;
+    let udp = match ip.protocol() {
+        IP_PROTOCOL_UDP if !ip.is_fragment() => { Some(udp_datagram(ip.payload())?) }
+        _ => { None }
+    };
+
+    Ok(DecodedPacket {
+        link,
+        ip,
+        udp,
+    })
+}
+
+/// Splits the packet data into the link-layer header, the EtherType of the payload, and the payload.
+pub(crate) fn link_layer(link_type: u32, data: &[u8]) -> Result<(LinkHeader, u16, &[u8]), DecodeError> {
+    // The upper bits of a .pcap link type describe the frame check sequence, not the link layer.
+    match link_type & 0xFFFF {
+        LINKTYPE_ETHERNET => {
+            let (header, payload) = ethernet_frame(data)?;
+            let ether_type = header.ether_type;
+            Ok((LinkHeader::Ethernet(header), ether_type, payload))
+        }
+        LINKTYPE_LINUX_SLL => {
+            let (header, payload) = linux_sll(data)?;
+            let ether_type = header.protocol;
+            Ok((LinkHeader::LinuxSll(header), ether_type, payload))
+        }
+        LINKTYPE_LINUX_SLL2 => {
+            let (header, payload) = linux_sll2(data)?;
+            let ether_type = header.protocol;
+            Ok((LinkHeader::LinuxSll2(header), ether_type, payload))
+        }
+        LINKTYPE_NULL | LINKTYPE_LOOP => {
+            let (family, payload) = loopback(link_type & 0xFFFF, data)?;
+            Ok((LinkHeader::Loopback { family }, loopback_ether_type(family)?, payload))
+        }
+        LINKTYPE_RAW => { Ok((LinkHeader::Raw, raw_ip_ether_type(data)?, data)) }
+        LINKTYPE_IPV4 => { Ok((LinkHeader::Raw, ETHERTYPE_IPV4, data)) }
+        LINKTYPE_IPV6 => { Ok((LinkHeader::Raw, ETHERTYPE_IPV6, data)) }
+        _ => { Err(DecodeError::UnsupportedLinkType(link_type)) }
+    }
+}
+
+/// Maps a nom error to `DecodeError::Truncated`, as the decoders only fail when running out of data.
+fn truncated(header: &'static str) -> impl Fn(nom::Err<nom::error::Error<&[u8]>>) -> DecodeError {
+    move |_err| DecodeError::Truncated(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use super::*;
+
+    const UDP_HEADER: [u8; 8] = [0x30, 0x39, 0x00, 0x35, 0x00, 0x0B, 0x00, 0x00];
+
+    #[test]
+    fn decode_vlan_tagged_ipv4_with_options() {
+        let mut frame = vec![0xFF; 12];
+        frame.extend_from_slice(&[0x88, 0xA8, 0x00, 0x64, 0x81, 0x00, 0x20, 0x0A, 0x08, 0x00]);
+        frame.extend_from_slice(&[0x46, 0, 0, 35, 0, 0, 0, 0, 64, 17, 0, 0, 10, 0, 0, 1, 239, 1, 2, 3, 1, 1, 1, 0]);
+        frame.extend_from_slice(&UDP_HEADER);
+        frame.extend_from_slice(b"abc");
+        frame.resize(64, 0);    // Ethernet padding
+
+        let packet = decode_packet(LINKTYPE_ETHERNET, &frame).unwrap();
+        let LinkHeader::Ethernet(ethernet) = &packet.link else { panic!("Expected an Ethernet header") };
+        assert_eq!(ethernet.vlan_tags.iter().map(|tag| tag.vlan_id).collect::<Vec<_>>(), vec![100, 10]);
+        assert_eq!(packet.ip.destination(), IpAddr::V4(Ipv4Addr::new(239, 1, 2, 3)));
+        let udp = packet.udp.unwrap();
+        assert_eq!((udp.source_port, udp.destination_port), (12345, 53));
+        assert_eq!(udp.payload, b"abc");
+    }
+
+    #[test]
+    fn decode_ipv6_with_extension_headers() {
+        let mut frame = vec![0xFF; 12];
+        frame.extend_from_slice(&[0x86, 0xDD]);
+        frame.extend_from_slice(&[0x60, 0, 0, 0, 0, 27, IP_PROTOCOL_HOPOPT, 64]);
+        frame.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        frame.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        frame.extend_from_slice(&[IP_PROTOCOL_IPV6_OPTS, 0, 1, 4, 0, 0, 0, 0]);
+        frame.extend_from_slice(&[IP_PROTOCOL_UDP, 0, 1, 4, 0, 0, 0, 0]);
+        frame.extend_from_slice(&UDP_HEADER);
+        frame.extend_from_slice(b"abc");
+
+        let packet = decode_packet(LINKTYPE_ETHERNET, &frame).unwrap();
+        assert_eq!(packet.ip.protocol(), IP_PROTOCOL_UDP);
+        assert_eq!(packet.udp.unwrap().payload, b"abc");
+    }
+
+    #[test]
+    fn decode_cooked_loopback_and_raw_captures() {
+        let mut ip = vec![0x45, 0, 0, 31, 0, 0, 0, 0, 64, 17, 0, 0, 127, 0, 0, 1, 127, 0, 0, 1];
+        ip.extend_from_slice(&UDP_HEADER);
+        ip.extend_from_slice(b"abc");
+
+        let mut sll = vec![0, 4, 0, 1, 0, 6, 1, 2, 3, 4, 5, 6, 0, 0, 0x08, 0x00];
+        sll.extend_from_slice(&ip);
+        let mut sll2 = vec![0x08, 0x00, 0, 0, 0, 0, 0, 3, 0, 1, 4, 6, 1, 2, 3, 4, 5, 6, 0, 0];
+        sll2.extend_from_slice(&ip);
+        let mut null = vec![2, 0, 0, 0];
+        null.extend_from_slice(&ip);
+        let mut null_swapped = vec![0, 0, 0, 2];
+        null_swapped.extend_from_slice(&ip);
+        let mut loop_ = vec![0, 0, 0, 2];
+        loop_.extend_from_slice(&ip);
+
+        let captures = [(LINKTYPE_LINUX_SLL, &sll), (LINKTYPE_LINUX_SLL2, &sll2), (LINKTYPE_NULL, &null),
+            (LINKTYPE_NULL, &null_swapped), (LINKTYPE_LOOP, &loop_), (LINKTYPE_RAW, &ip), (LINKTYPE_IPV4, &ip)];
+        for (link_type, data) in captures {
+            let packet = decode_packet(link_type, data).unwrap();
+            assert_eq!(packet.udp.unwrap().payload, b"abc");
+        }
+        let packet = decode_packet(LINKTYPE_LINUX_SLL2, &sll2).unwrap();
+        let LinkHeader::LinuxSll2(header) = &packet.link else { panic!("Expected a Linux cooked header") };
+        assert_eq!((header.interface_index, header.packet_type, header.address.len()), (Some(3), 4, 6));
+        assert_eq!(decode_packet(147, &ip), Err(DecodeError::UnsupportedLinkType(147)));
+    }
+
+    #[test]
+    fn skip_udp_of_fragments() {
+        let mut frame = vec![0xFF; 12];
+        frame.extend_from_slice(&[0x08, 0x00]);
+        frame.extend_from_slice(&[0x45, 0, 0, 31, 0, 1, 0x20, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        frame.extend_from_slice(&UDP_HEADER);
+        frame.extend_from_slice(b"abc");
+
+        let packet = decode_packet(LINKTYPE_ETHERNET, &frame).unwrap();
+        assert!(packet.ip.is_fragment());
+        assert_eq!(packet.udp, None);
+        assert_eq!(decode_packet(LINKTYPE_ETHERNET, &frame[..20]), Err(DecodeError::Truncated("IPv4")));
+    }
+}
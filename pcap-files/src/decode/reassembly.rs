@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::time::Duration;
+use crate::constants::IP_PROTOCOL_UDP;
+use crate::decode::DecodeError;
+use crate::decode::ip::{extension_headers, IpPacket};
+use crate::decode::udp::{udp_datagram, UdpDatagram};
+
+/// How long fragments wait for the rest of their datagram, as used by Linux.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Reassembles IPv4 and IPv6 datagrams from their fragments, which are pushed in capture order.
+/// Fragment sets that are not completed within the timeout are reported as incomplete.
+#[derive(Debug)]
+pub struct FragmentReassembler {
+    pending: HashMap<FragmentKey, FragmentSet>,
+    incomplete: Vec<IncompleteDatagram>,
+    timeout: Duration,
+}
+
+/// A datagram reassembled from its fragments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReassembledPacket {
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    pub protocol: u8,           // for IPv6 this can be an extension header preceding the upper-layer protocol
+    pub identification: u32,
+    pub payload: Vec<u8>,
+    pub fragment_count: usize,
+}
+
+/// A datagram of which not all fragments were captured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IncompleteDatagram {
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    pub protocol: u8,
+    pub identification: u32,
+    pub first_timestamp: Duration,
+    pub fragment_count: usize,
+    pub received_bytes: usize,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct FragmentKey {
+    source: IpAddr,
+    destination: IpAddr,
+    protocol: u8,
+    identification: u32,
+}
+
+#[derive(Debug)]
+struct FragmentSet {
+    first_timestamp: Duration,
+    fragments: Vec<(usize, Vec<u8>)>,   // offset and data, in order of arrival
+    total_length: Option<usize>,        // known once the last fragment arrived
+}
+
+impl FragmentReassembler {
+    pub fn new() -> Self {
+        Self {
+            pending: HashMap::new(),
+            incomplete: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Adds the fragment captured at `timestamp`, returning the reassembled datagram when this completes it.
+    /// Packets that are not fragments are ignored.
+    pub fn push(&mut self, timestamp: Duration, packet: &IpPacket) -> Option<ReassembledPacket> {
+        self.expire(timestamp);
+        let fragment = packet.fragment()?;
+        let key = FragmentKey {
+            source: packet.source(),
+            destination: packet.destination(),
+            protocol: packet.protocol(),
+            identification: fragment.identification,
+        };
+
+        let set = self.pending.entry(key.clone()).or_insert_with(|| FragmentSet {
+            first_timestamp: timestamp,
+            fragments: Vec::new(),
+            total_length: None,
+        });
+        let offset = fragment.fragment_offset as usize;
+        if !fragment.more_fragments {
+            set.total_length = Some(offset + packet.payload().len());
+        }
+        set.fragments.push((offset, packet.payload().to_vec()));
+
+        let payload = set.assemble()?;
+        let fragment_count = set.fragments.len();
+        self.pending.remove(&key);
+        Some(ReassembledPacket {
+            source: key.source,
+            destination: key.destination,
+            protocol: key.protocol,
+            identification: key.identification,
+            payload,
+            fragment_count,
+        })
+    }
+
+    /// Takes the datagrams that expired so far without being completed, in order of their first fragment.
+    /// These are no longer returned by `finish`.
+    pub fn take_incomplete(&mut self) -> Vec<IncompleteDatagram> {
+        let mut incomplete = std::mem::take(&mut self.incomplete);
+        incomplete.sort_by_key(|datagram| datagram.first_timestamp);
+        incomplete
+    }
+
+    /// Ends the reassembly, returning all datagrams that were not completed in order of their first fragment.
+    pub fn finish(mut self) -> Vec<IncompleteDatagram> {
+        let pending: Vec<FragmentKey> = self.pending.keys().cloned().collect();
+        self.remove_incomplete(pending);
+        self.incomplete.sort_by_key(|datagram| datagram.first_timestamp);
+        self.incomplete
+    }
+
+    fn expire(&mut self, now: Duration) {
+        let expired: Vec<FragmentKey> = self.pending.iter()
+            .filter(|(_, set)| now.saturating_sub(set.first_timestamp) > self.timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        self.remove_incomplete(expired);
+    }
+
+    fn remove_incomplete(&mut self, keys: Vec<FragmentKey>) {
+        for key in keys {
+            if let Some(set) = self.pending.remove(&key) {
+                self.incomplete.push(IncompleteDatagram {
+                    source: key.source,
+                    destination: key.destination,
+                    protocol: key.protocol,
+                    identification: key.identification,
+                    first_timestamp: set.first_timestamp,
+                    fragment_count: set.fragments.len(),
+                    received_bytes: set.fragments.iter().map(|(_, data)| data.len()).sum(),
+                });
+            }
+        }
+    }
+}
+
+impl Default for FragmentReassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FragmentSet {
+    /// The complete payload, if the fragments cover it without gaps. Where fragments overlap, the data
+    /// of the later fragment is used.
+    fn assemble(&self) -> Option<Vec<u8>> {
+        let total_length = self.total_length?;
+        let mut by_offset: Vec<&(usize, Vec<u8>)> = self.fragments.iter().collect();
+        by_offset.sort_by_key(|(offset, _)| *offset);
+        let mut covered = 0;
+        for (offset, data) in by_offset {
+            if *offset > covered {
+                return None;
+            }
+            covered = covered.max(offset + data.len());
+        }
+        if covered < total_length {
+            return None;
+        }
+
+        let mut payload = vec![0u8; total_length];
+        for (offset, data) in &self.fragments {
+            let end = (offset + data.len()).min(total_length);
+            if *offset < end {
+                payload[*offset..end].copy_from_slice(&data[..end - offset]);
+            }
+        }
+        Some(payload)
+    }
+}
+
+impl ReassembledPacket {
+    /// Decodes the reassembled payload as a UDP datagram, if it is one.
+    pub fn udp(&self) -> Result<Option<UdpDatagram<'_>>, DecodeError> {
+        let (protocol, payload) = match self.source {
+            IpAddr::V4(_) => { (self.protocol, self.payload.as_slice()) }
+            IpAddr::V6(_) => {
+                let (protocol, _fragment, payload) = extension_headers(self.protocol, &self.payload)?;
+                (protocol, payload)
+            }
+        };
+        match protocol {
+            IP_PROTOCOL_UDP => { udp_datagram(payload).map(Some) }
+            _ => { Ok(None) }
+        }
+    }
+}
+
+impl Display for IncompleteDatagram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "datagram {} from {} to {} is incomplete, received {} fragments with {} bytes",
+               self.identification, self.source, self.destination, self.fragment_count, self.received_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv6Addr;
+    use crate::constants::*;
+    use crate::decode::decode_packet;
+    use super::*;
+
+    fn ipv4_fragment(identification: u16, offset: u16, more_fragments: bool, data: &[u8]) -> Vec<u8> {
+        let flags_and_offset = (offset >> 3) | if more_fragments { 0x2000 } else { 0 };
+        let mut packet = vec![0x45, 0];
+        packet.extend_from_slice(&(20 + data.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&identification.to_be_bytes());
+        packet.extend_from_slice(&flags_and_offset.to_be_bytes());
+        packet.extend_from_slice(&[64, IP_PROTOCOL_UDP, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    fn udp(payload: &[u8]) -> Vec<u8> {
+        let mut datagram = vec![0x30, 0x39, 0x00, 0x35];
+        datagram.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        datagram.extend_from_slice(&[0, 0]);
+        datagram.extend_from_slice(payload);
+        datagram
+    }
+
+    #[test]
+    fn reassemble_out_of_order_fragments_and_report_incomplete() {
+        let payload: Vec<u8> = (0..40).collect();
+        let datagram = udp(&payload);
+        let fragments = [
+            ipv4_fragment(1, 16, true, &datagram[16..32]),
+            ipv4_fragment(2, 0, true, &datagram[..16]),
+            ipv4_fragment(1, 32, false, &datagram[32..]),
+            ipv4_fragment(1, 0, true, &datagram[..16]),
+        ];
+
+        let mut reassembler = FragmentReassembler::new();
+        let mut reassembled = Vec::new();
+        for (i, fragment) in fragments.iter().enumerate() {
+            let packet = decode_packet(LINKTYPE_IPV4, fragment).unwrap();
+            assert_eq!(packet.udp, None);
+            reassembled.extend(reassembler.push(Duration::from_secs(i as u64), &packet.ip));
+        }
+        assert_eq!(reassembled.len(), 1);
+        assert_eq!(reassembled[0].fragment_count, 3);
+        assert_eq!(reassembled[0].udp().unwrap().unwrap().payload, payload.as_slice());
+
+        let incomplete = reassembler.finish();
+        assert_eq!(incomplete.len(), 1);
+        assert_eq!((incomplete[0].identification, incomplete[0].received_bytes), (2, 16));
+    }
+
+    #[test]
+    fn take_expired_datagrams() {
+        let datagram = udp(&[0; 24]);
+        let mut reassembler = FragmentReassembler::new().with_timeout(Duration::from_secs(5));
+        let (first, other) = (ipv4_fragment(1, 0, true, &datagram[..16]), ipv4_fragment(2, 0, true, &datagram[..16]));
+        let first = decode_packet(LINKTYPE_IPV4, &first).unwrap();
+        let other = decode_packet(LINKTYPE_IPV4, &other).unwrap();
+        reassembler.push(Duration::ZERO, &first.ip);
+        assert!(reassembler.take_incomplete().is_empty());
+
+        reassembler.push(Duration::from_secs(6), &other.ip);
+        let expired = reassembler.take_incomplete();
+        assert_eq!(expired.len(), 1);
+        assert_eq!((expired[0].protocol, expired[0].identification), (IP_PROTOCOL_UDP, 1));
+        let incomplete = reassembler.finish();
+        assert_eq!(incomplete.len(), 1);
+        assert_eq!(incomplete[0].identification, 2);
+    }
+
+    #[test]
+    fn reassemble_ipv6_fragments() {
+        let payload: Vec<u8> = (0..40).collect();
+        let datagram = udp(&payload);
+        let fragment = |offset: u16, more_fragments: bool, data: &[u8]| {
+            let mut packet = vec![0x60, 0, 0, 0];
+            packet.extend_from_slice(&(8 + data.len() as u16).to_be_bytes());
+            packet.extend_from_slice(&[IP_PROTOCOL_IPV6_FRAG, 64]);
+            packet.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+            packet.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+            packet.extend_from_slice(&[IP_PROTOCOL_UDP, 0]);
+            packet.extend_from_slice(&(offset | more_fragments as u16).to_be_bytes());
+            packet.extend_from_slice(&7u32.to_be_bytes());
+            packet.extend_from_slice(data);
+            packet
+        };
+
+        let mut reassembler = FragmentReassembler::new();
+        let first = fragment(0, true, &datagram[..24]);
+        let last = fragment(24, false, &datagram[24..]);
+        assert_eq!(reassembler.push(Duration::ZERO, &decode_packet(LINKTYPE_IPV6, &first).unwrap().ip), None);
+        let reassembled = reassembler.push(Duration::ZERO, &decode_packet(LINKTYPE_IPV6, &last).unwrap().ip).unwrap();
+        assert_eq!(reassembled.udp().unwrap().unwrap().payload, payload.as_slice());
+        assert!(reassembler.finish().is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+use nom::IResult;
+use nom::number::complete::be_u16;
+use crate::constants::UDP_HEADER_LENGTH_BYTES;
+use crate::decode::{truncated, DecodeError};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UdpDatagram<'a> {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub length: u16,            // of header and payload
+    pub checksum: u16,
+    pub payload: &'a [u8],      // excluding any padding following the datagram
+}
+
+pub(crate) fn udp_datagram(data: &[u8]) -> Result<UdpDatagram<'_>, DecodeError> {
+    let (payload, (source_port, destination_port, length, checksum)) = udp_header(data).map_err(truncated("UDP"))?;
+    // A length of 0 is used by jumbograms, and by datagrams offloaded for segmentation.
+    let payload = match length {
+        0 => { payload }
+        length if length < UDP_HEADER_LENGTH_BYTES => { return Err(DecodeError::Invalid("UDP")); }
+        length => { &payload[..((length - UDP_HEADER_LENGTH_BYTES) as usize).min(payload.len())] }
+    };
+
+    Ok(UdpDatagram {
+        source_port,
+        destination_port,
+        length,
+        checksum,
+        payload,
+    })
+}
+
+fn udp_header(input: &[u8]) -> IResult<&[u8], (u16, u16, u16, u16)> {
+    let (input, source_port) = be_u16(input)?;
+    let (input, destination_port) = be_u16(input)?;
+    let (input, length) = be_u16(input)?;
+    let (input, checksum) = be_u16(input)?;
+    Ok((input, (source_port, destination_port, length, checksum)))
+}
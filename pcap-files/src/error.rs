@@ -0,0 +1,155 @@
+use std::fmt::{Display, Formatter};
+use nom::error::{ContextError, ErrorKind};
+use nom::IResult;
+use thiserror::Error;
+
+/// Describes where and why parsing a capture failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub offset: usize,                  // byte offset from the start of the file
+    pub packet_index: Option<usize>,    // 0-based index of the packet being parsed, if any
+    pub field: Option<&'static str>,
+    pub reason: ParseErrorReason,
+}
+
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum ParseErrorReason {
+    #[error("unknown magic number {0:#010X}")]
+    UnknownMagicNumber(u32),
+    #[error("unsupported version {major}.{minor}")]
+    UnsupportedVersion { major: u16, minor: u16 },
+    #[error("unexpected end of data")]
+    Truncated,
+    #[error("captured length {captured_length} exceeds the snap length {snap_len}")]
+    CapturedLengthExceedsSnapLen { captured_length: u32, snap_len: u32 },
+    #[error("invalid block length {0}")]
+    InvalidBlockLength(u32),
+    #[error("reference to undefined interface {0}")]
+    UnknownInterface(u32),
+    #[error("malformed data")]
+    Malformed,
+    #[error("I/O error: {0}")]
+    Io(String),
+}
+
+impl ParseError {
+    pub fn new(offset: usize, reason: ParseErrorReason) -> Self {
+        Self {
+            offset,
+            packet_index: None,
+            field: None,
+            reason,
+        }
+    }
+
+    pub fn with_packet_index(mut self, packet_index: usize) -> Self {
+        self.packet_index = Some(packet_index);
+        self
+    }
+
+    pub fn with_field(mut self, field: &'static str) -> Self {
+        self.field = Some(field);
+        self
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte offset {}", self.reason, self.offset)?;
+        if let Some(field) = self.field {
+            write!(f, ", parsing the {field}")?;
+        }
+        if let Some(packet_index) = self.packet_index {
+            write!(f, " of packet {}", packet_index + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub(crate) type ParseResult<'a, T> = IResult<&'a [u8], T, NomError<'a>>;
+
+/// Error type of the nom parsers, collecting the location and the field being parsed on failure.
+#[derive(Debug)]
+pub(crate) struct NomError<'a> {
+    input: &'a [u8],
+    kind: ErrorKind,
+    field: Option<&'static str>,
+    reason: Option<ParseErrorReason>,
+    packet_index: Option<usize>,
+}
+
+impl<'a> NomError<'a> {
+    /// A failure that is not caused by running out of input, such as an unsupported value of `field`.
+    pub fn failure(input: &'a [u8], field: &'static str, reason: ParseErrorReason) -> nom::Err<Self> {
+        nom::Err::Failure(Self {
+            input,
+            kind: ErrorKind::Verify,
+            field: Some(field),
+            reason: Some(reason),
+            packet_index: None,
+        })
+    }
+
+    /// Converts to a `ParseError`, locating the failure relative to `base`, the complete file buffer.
+    pub fn into_parse_error(self, base: &[u8]) -> ParseError {
+        let reason = self.reason.unwrap_or(match self.kind {
+            ErrorKind::Eof => ParseErrorReason::Truncated,
+            _ => ParseErrorReason::Malformed,
+        });
+        ParseError {
+            offset: offset_in(base, self.input),
+            packet_index: self.packet_index,
+            field: self.field,
+            reason,
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<&'a [u8]> for NomError<'a> {
+    fn from_error_kind(input: &'a [u8], kind: ErrorKind) -> Self {
+        Self {
+            input,
+            kind,
+            field: None,
+            reason: None,
+            packet_index: None,
+        }
+    }
+
+    fn append(_input: &'a [u8], _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'a> ContextError<&'a [u8]> for NomError<'a> {
+    fn add_context(_input: &'a [u8], ctx: &'static str, mut other: Self) -> Self {
+        // keep the innermost field
+        if other.field.is_none() {
+            other.field = Some(ctx);
+        }
+        other
+    }
+}
+
+/// Marks the error of a parser, if any, as occurring in the packet at `packet_index`.
+pub(crate) fn in_packet<'a, T>(packet_index: usize, result: ParseResult<'a, T>) -> ParseResult<'a, T> {
+    result.map_err(|err| err.map(|mut err| {
+        err.packet_index.get_or_insert(packet_index);
+        err
+    }))
+}
+
+/// Converts the error of a parser run on `base`, or on a slice of it, to a `ParseError`.
+pub(crate) fn parse_error(base: &[u8], err: nom::Err<NomError>) -> ParseError {
+    match err {
+        nom::Err::Error(err) | nom::Err::Failure(err) => { err.into_parse_error(base) }
+        nom::Err::Incomplete(_) => { ParseError::new(base.len(), ParseErrorReason::Truncated) }
+    }
+}
+
+/// Byte offset of `slice` within `base`, given that `slice` points into `base`.
+pub(crate) fn offset_in(base: &[u8], slice: &[u8]) -> usize {
+    (slice.as_ptr() as usize).saturating_sub(base.as_ptr() as usize)
+}
@@ -0,0 +1,354 @@
+pub(crate) mod parser;
+
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+use thiserror::Error;
+use crate::constants::*;
+use crate::decode::{link_layer, LinkHeader};
+use crate::decode::ip::{ip_packet, IpPacket};
+
+/// A filter in the syntax of tcpdump and libpcap (see `pcap-filter(7)`), evaluated in Rust on the captured data.
+///
+/// The supported subset consists of:
+/// - `[src|dst|src or dst|src and dst] host ADDR` and `... net ADDR/LEN` or `... net ADDR mask MASK`
+/// - `[tcp|udp] [src|dst|...] port PORT` and `... portrange PORT-PORT`
+/// - `ip`, `ip6`, `udp`, `tcp`, `icmp`, `icmp6`, `[ip|ip6] proto PROTO` and `vlan [ID]`, matching any VLAN tag
+/// - `greater LEN`, `less LEN` and comparisons of arithmetic expressions, such as `udp[8:2] & 0xFF00 = 0x0500`,
+///   over `len` and the bytes of `ether`, `ip`, `ip6`, `tcp`, `udp` and `icmp`
+/// - `and`, `or`, `not` (or `&&`, `||`, `!`) and parentheses
+///
+/// Unlike tcpdump, a qualifier is not carried over to an abbreviated next primitive, so write
+/// `port 3000 or port 3001` rather than `port 3000 or 3001`. `len` is the captured length of the packet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BpfFilter {
+    expression: String,
+    filter: Option<Expr>,  // None for the empty expression, which matches all packets
+}
+
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error("invalid filter expression at position {position}: expected {expected}")]
+pub struct FilterError {
+    pub position: usize,    // character offset in the expression
+    pub expected: &'static str,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Primitive(Primitive),
+    Compare(Arith, Relation, Arith),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Primitive {
+    Host(Direction, IpAddr),
+    Net(Direction, IpAddr, u8),                 // address and prefix length
+    Port(Direction, Option<u8>, u16, u16),      // optional protocol, and the inclusive port range
+    Ip,
+    Ip6,
+    Protocol(u8),
+    Vlan(Option<u16>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Direction {
+    Source,
+    Destination,
+    SourceOrDestination,
+    SourceAndDestination,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Arith {
+    Number(u32),
+    Length,
+    Load { layer: Layer, offset: Box<Arith>, size: u8 },
+    Binary(Box<Arith>, ArithOp, Box<Arith>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Layer {
+    Link,
+    Ip,
+    Ip6,
+    Tcp,
+    Udp,
+    Icmp,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum ArithOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    And,
+    Or,
+    ShiftLeft,
+    ShiftRight,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum Relation {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// The layers of a packet that a filter refers to, decoded once per packet.
+struct PacketView<'a> {
+    data: &'a [u8],
+    link: Option<LinkHeader>,
+    ip: Option<(usize, IpPacket<'a>)>,  // with the offset of the IP header
+    transport: Option<usize>,           // offset of the TCP/UDP/ICMP header, if this is not a later fragment
+}
+
+impl BpfFilter {
+    pub fn new(expression: &str) -> Result<Self, FilterError> {
+        let filter = match expression.trim() {
+            "" => { None }
+            _ => { Some(parser::parse(expression)?) }
+        };
+        Ok(Self {
+            expression: expression.to_string(),
+            filter,
+        })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Whether the packet data, captured with the given link type, matches the filter. As with libpcap,
+    /// a packet does not match when the filter reads beyond its captured data.
+    pub fn matches(&self, link_type: u32, data: &[u8]) -> bool {
+        match &self.filter {
+            None => { true }
+            Some(filter) => { filter.eval(&PacketView::new(link_type, data)).unwrap_or(false) }
+        }
+    }
+}
+
+impl FromStr for BpfFilter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl Display for BpfFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl<'a> PacketView<'a> {
+    fn new(link_type: u32, data: &'a [u8]) -> Self {
+        let Ok((link, ether_type, payload)) = link_layer(link_type, data) else {
+            return Self { data, link: None, ip: None, transport: None };
+        };
+        let ip = ip_packet(ether_type, payload).ok();
+        let transport = ip.as_ref()
+            .filter(|ip| ip.fragment().is_none_or(|fragment| fragment.fragment_offset == 0))
+            .map(|ip| offset_in(data, ip.payload()));
+        Self {
+            data,
+            link: Some(link),
+            ip: ip.map(|ip| (offset_in(data, payload), ip)),
+            transport,
+        }
+    }
+
+    fn load(&self, offset: usize, size: u8) -> Option<u32> {
+        let bytes = self.data.get(offset..offset.checked_add(size as usize)?)?;
+        Some(bytes.iter().fold(0, |value, byte| value << 8 | *byte as u32))
+    }
+
+    fn protocol(&self) -> Option<u8> {
+        self.ip.as_ref().map(|(_, ip)| ip.protocol())
+    }
+
+    /// The source and destination port, for TCP and UDP.
+    fn ports(&self) -> Option<(u16, u16)> {
+        match self.protocol()? {
+            IP_PROTOCOL_TCP | IP_PROTOCOL_UDP => {
+                let transport = self.transport?;
+                Some((self.load(transport, 2)? as u16, self.load(transport + 2, 2)? as u16))
+            }
+            _ => { None }
+        }
+    }
+
+    fn layer_offset(&self, layer: Layer) -> Option<usize> {
+        match (layer, &self.ip) {
+            (Layer::Link, _) => { Some(0) }
+            (Layer::Ip, Some((offset, IpPacket::V4(_)))) | (Layer::Ip6, Some((offset, IpPacket::V6(_)))) => {
+                Some(*offset)
+            }
+            (Layer::Tcp, _) if self.protocol() == Some(IP_PROTOCOL_TCP) => { self.transport }
+            (Layer::Udp, _) if self.protocol() == Some(IP_PROTOCOL_UDP) => { self.transport }
+            (Layer::Icmp, _) if self.protocol() == Some(IP_PROTOCOL_ICMP) => { self.transport }
+            _ => { None }
+        }
+    }
+}
+
+fn offset_in(data: &[u8], part: &[u8]) -> usize {
+    part.as_ptr() as usize - data.as_ptr() as usize
+}
+
+impl Expr {
+    /// Evaluates the expression, or `None` when it reads beyond the packet data.
+    fn eval(&self, packet: &PacketView) -> Option<bool> {
+        match self {
+            Expr::And(left, right) => { Some(left.eval(packet)? && right.eval(packet)?) }
+            Expr::Or(left, right) => { Some(left.eval(packet)? || right.eval(packet)?) }
+            Expr::Not(expr) => { expr.eval(packet).map(|matches| !matches) }
+            Expr::Primitive(primitive) => { Some(primitive.matches(packet)) }
+            Expr::Compare(left, relation, right) => {
+                let (left, right) = (left.eval(packet)?, right.eval(packet)?);
+                Some(match relation {
+                    Relation::Equal => { left == right }
+                    Relation::NotEqual => { left != right }
+                    Relation::Less => { left < right }
+                    Relation::LessOrEqual => { left <= right }
+                    Relation::Greater => { left > right }
+                    Relation::GreaterOrEqual => { left >= right }
+                })
+            }
+        }
+    }
+}
+
+impl Primitive {
+    fn matches(&self, packet: &PacketView) -> bool {
+        match self {
+            Primitive::Host(direction, address) => {
+                packet.ip.as_ref().is_some_and(|(_, ip)| {
+                    direction.matches(ip.source(), ip.destination(), |ip| ip == *address)
+                })
+            }
+            Primitive::Net(direction, network, prefix_length) => {
+                packet.ip.as_ref().is_some_and(|(_, ip)| {
+                    direction.matches(ip.source(), ip.destination(), |ip| in_network(ip, *network, *prefix_length))
+                })
+            }
+            Primitive::Port(direction, protocol, low, high) => {
+                protocol.is_none_or(|protocol| packet.protocol() == Some(protocol))
+                    && packet.ports().is_some_and(|(source, destination)| {
+                        direction.matches(source, destination, |port| (*low..=*high).contains(&port))
+                    })
+            }
+            Primitive::Ip => { matches!(packet.ip, Some((_, IpPacket::V4(_)))) }
+            Primitive::Ip6 => { matches!(packet.ip, Some((_, IpPacket::V6(_)))) }
+            Primitive::Protocol(protocol) => { packet.protocol() == Some(*protocol) }
+            Primitive::Vlan(vlan_id) => {
+                match &packet.link {
+                    Some(LinkHeader::Ethernet(ethernet)) => {
+                        ethernet.vlan_tags.iter().any(|tag| vlan_id.is_none_or(|vlan_id| tag.vlan_id == vlan_id))
+                    }
+                    _ => { false }
+                }
+            }
+        }
+    }
+}
+
+impl Direction {
+    fn matches<T: Copy>(&self, source: T, destination: T, matches: impl Fn(T) -> bool) -> bool {
+        match self {
+            Direction::Source => { matches(source) }
+            Direction::Destination => { matches(destination) }
+            Direction::SourceOrDestination => { matches(source) || matches(destination) }
+            Direction::SourceAndDestination => { matches(source) && matches(destination) }
+        }
+    }
+}
+
+fn in_network(address: IpAddr, network: IpAddr, prefix_length: u8) -> bool {
+    match (address, network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix_length as u32).unwrap_or(0);
+            u32::from(address) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(address), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix_length as u32).unwrap_or(0);
+            u128::from(address) & mask == u128::from(network) & mask
+        }
+        _ => { false }
+    }
+}
+
+impl Arith {
+    fn eval(&self, packet: &PacketView) -> Option<u32> {
+        match self {
+            Arith::Number(value) => { Some(*value) }
+            Arith::Length => { Some(packet.data.len() as u32) }
+            Arith::Load { layer, offset, size } => {
+                let offset = packet.layer_offset(*layer)?.checked_add(offset.eval(packet)? as usize)?;
+                packet.load(offset, *size)
+            }
+            Arith::Binary(left, op, right) => {
+                let (left, right) = (left.eval(packet)?, right.eval(packet)?);
+                match op {
+                    ArithOp::Add => { Some(left.wrapping_add(right)) }
+                    ArithOp::Subtract => { Some(left.wrapping_sub(right)) }
+                    ArithOp::Multiply => { Some(left.wrapping_mul(right)) }
+                    ArithOp::Divide => { left.checked_div(right) }
+                    ArithOp::And => { Some(left & right) }
+                    ArithOp::Or => { Some(left | right) }
+                    ArithOp::ShiftLeft => { Some(left.checked_shl(right).unwrap_or(0)) }
+                    ArithOp::ShiftRight => { Some(left.checked_shr(right).unwrap_or(0)) }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An Ethernet frame with a VLAN tag, carrying a UDP datagram from 10.0.0.1:3000 to 239.1.2.3:3001.
+    fn vlan_udp_frame() -> Vec<u8> {
+        let mut frame = vec![0xFF; 12];
+        frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x64, 0x08, 0x00]);
+        frame.extend_from_slice(&[0x45, 0, 0, 31, 0, 0, 0, 0, 64, 17, 0, 0, 10, 0, 0, 1, 239, 1, 2, 3]);
+        frame.extend_from_slice(&[0x0B, 0xB8, 0x0B, 0xB9, 0x00, 0x0B, 0x00, 0x00]);
+        frame.extend_from_slice(b"abc");
+        frame
+    }
+
+    #[test]
+    fn match_primitives_and_operators() {
+        let frame = vlan_udp_frame();
+        let matches = |expression: &str| BpfFilter::new(expression).unwrap().matches(LINKTYPE_ETHERNET, &frame);
+
+        assert!(matches(""));
+        assert!(matches("udp and src host 10.0.0.1 and dst port 3001"));
+        assert!(matches("net 239.0.0.0/8 && vlan 100 && ip"));
+        assert!(matches("src net 10.0.0.0 mask 255.0.0.0 and udp portrange 2990-3000"));
+        assert!(matches("not (tcp or ip6) and !vlan 200"));
+        assert!(matches("udp[8:2] = 0x6162 and ip[9] == 17 and len > 40 and less 100"));
+        assert!(matches("ip[0] & 0xf = 5 and ip[2:2] - 20 = udp[4:2]"));
+        assert!(!matches("dst host 10.0.0.1"));
+        assert!(!matches("src and dst net 10.0.0.0/8"));
+        assert!(!matches("tcp port 3000"));
+        assert!(!matches("udp[100] = 0 or udp"), "reading beyond the packet rejects it");
+    }
+
+    #[test]
+    fn locate_syntax_errors() {
+        assert_eq!(BpfFilter::new("udp and").unwrap_err().position, 7);
+        assert_eq!(BpfFilter::new("host 10.0.0").unwrap_err(), FilterError { position: 5, expected: "an IP address" });
+        assert_eq!(BpfFilter::new("udp port 3000 3001").unwrap_err(),
+                   FilterError { position: 14, expected: "`and`, `or` or the end of the expression" });
+    }
+}
@@ -0,0 +1,267 @@
+use std::net::IpAddr;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while1};
+use nom::character::complete::{char, digit1, hex_digit1, multispace0, satisfy};
+use nom::combinator::{cut, map, map_res, not, opt, peek, value, verify};
+use nom::error::{context, VerboseError, VerboseErrorKind};
+use nom::IResult;
+use nom::multi::fold_many0;
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
+use crate::constants::*;
+use crate::filter::{Arith, ArithOp, Direction, Expr, FilterError, Layer, Primitive, Relation};
+
+type FilterResult<'a, T> = IResult<&'a str, T, VerboseError<&'a str>>;
+
+/// Parses a complete filter expression.
+pub(crate) fn parse(expression: &str) -> Result<Expr, FilterError> {
+    match or_expr(expression) {
+        Ok(("", expr)) => { Ok(expr) }
+        Ok((rest, _)) => {
+            Err(FilterError { position: expression.len() - rest.len(), expected: "`and`, `or` or the end of the expression" })
+        }
+        Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
+            // The first error is the innermost one, the first context is the most specific expectation.
+            let rest = err.errors.first().map_or("", |(rest, _)| *rest);
+            let expected = err.errors.iter()
+                .find_map(|(_, kind)| match kind {
+                    VerboseErrorKind::Context(expected) => { Some(*expected) }
+                    _ => { None }
+                })
+                .unwrap_or("a filter primitive");
+            Err(FilterError { position: expression.len() - rest.trim_start().len(), expected })
+        }
+        Err(nom::Err::Incomplete(_)) => { Err(FilterError { position: expression.len(), expected: "more input" }) }
+    }
+}
+
+fn or_expr(input: &str) -> FilterResult<'_, Expr> {
+    let (input, first) = and_expr(input)?;
+    fold_many0(
+        preceded(alt((keyword("or"), symbol("||"))), cut(and_expr)),
+        move || first.clone(),
+        |left, right| Expr::Or(Box::new(left), Box::new(right)),
+    )(input)
+}
+
+fn and_expr(input: &str) -> FilterResult<'_, Expr> {
+    let (input, first) = not_expr(input)?;
+    fold_many0(
+        preceded(alt((keyword("and"), symbol("&&"))), cut(not_expr)),
+        move || first.clone(),
+        |left, right| Expr::And(Box::new(left), Box::new(right)),
+    )(input)
+}
+
+fn not_expr(input: &str) -> FilterResult<'_, Expr> {
+    alt((
+        map(preceded(alt((keyword("not"), symbol_not_followed_by("!", '='))), cut(not_expr)),
+            |expr| Expr::Not(Box::new(expr))),
+        context("a filter primitive", alt((
+            map(preceded(keyword("greater"), cut(number)),
+                |length| Expr::Compare(Arith::Length, Relation::GreaterOrEqual, Arith::Number(length))),
+            map(preceded(keyword("less"), cut(number)),
+                |length| Expr::Compare(Arith::Length, Relation::LessOrEqual, Arith::Number(length))),
+            comparison,
+            delimited(symbol("("), or_expr, cut(context("`)`", symbol(")")))),
+            map(primitive, Expr::Primitive),
+        ))),
+    ))(input)
+}
+
+fn primitive(input: &str) -> FilterResult<'_, Primitive> {
+    alt((
+        map(pair(direction, preceded(keyword("host"), cut(address))),
+            |(direction, address)| Primitive::Host(direction, address)),
+        map(pair(direction, preceded(keyword("net"), cut(network))),
+            |(direction, (address, prefix_length))| Primitive::Net(direction, address, prefix_length)),
+        port_primitive,
+        map(preceded(opt(alt((keyword("ip6"), keyword("ip")))), preceded(keyword("proto"), cut(protocol))),
+            Primitive::Protocol),
+        map(preceded(keyword("vlan"), opt(map(number, |id| id as u16))), Primitive::Vlan),
+        value(Primitive::Ip6, keyword("ip6")),
+        value(Primitive::Ip, keyword("ip")),
+        map(protocol_name, Primitive::Protocol),
+    ))(input)
+}
+
+fn port_primitive(input: &str) -> FilterResult<'_, Primitive> {
+    let (input, protocol) = opt(alt((
+        value(IP_PROTOCOL_TCP, keyword("tcp")),
+        value(IP_PROTOCOL_UDP, keyword("udp")),
+    )))(input)?;
+    let (input, direction) = direction(input)?;
+    let (input, (low, high)) = alt((
+        preceded(keyword("portrange"), cut(context("a port range", separated_pair(port, symbol("-"), port)))),
+        map(preceded(keyword("port"), cut(port)), |port| (port, port)),
+    ))(input)?;
+    Ok((input, Primitive::Port(direction, protocol, low, high)))
+}
+
+fn direction(input: &str) -> FilterResult<'_, Direction> {
+    map(opt(alt((
+        value(Direction::SourceOrDestination, tuple((keyword("src"), keyword("or"), keyword("dst")))),
+        value(Direction::SourceAndDestination, tuple((keyword("src"), keyword("and"), keyword("dst")))),
+        value(Direction::Source, keyword("src")),
+        value(Direction::Destination, keyword("dst")),
+    ))), |direction| direction.unwrap_or(Direction::SourceOrDestination))(input)
+}
+
+fn protocol(input: &str) -> FilterResult<'_, u8> {
+    context("a protocol", alt((protocol_name, map_res(number, u8::try_from))))(input)
+}
+
+fn protocol_name(input: &str) -> FilterResult<'_, u8> {
+    alt((
+        value(IP_PROTOCOL_UDP, keyword("udp")),
+        value(IP_PROTOCOL_TCP, keyword("tcp")),
+        value(IP_PROTOCOL_ICMPV6, keyword("icmp6")),
+        value(IP_PROTOCOL_ICMP, keyword("icmp")),
+    ))(input)
+}
+
+fn port(input: &str) -> FilterResult<'_, u16> {
+    context("a port number", map_res(number, u16::try_from))(input)
+}
+
+fn address(input: &str) -> FilterResult<'_, IpAddr> {
+    context("an IP address", ws(map_res(
+        take_while1(|c: char| c.is_ascii_hexdigit() || c == '.' || c == ':'),
+        str::parse::<IpAddr>,
+    )))(input)
+}
+
+/// A network as `ADDR/LEN`, `ADDR mask MASK`, or a single address.
+fn network(input: &str) -> FilterResult<'_, (IpAddr, u8)> {
+    let (input, network) = address(input)?;
+    let bits = if network.is_ipv4() { 32 } else { 128 };
+    let (input, prefix_length) = opt(alt((
+        preceded(symbol("/"), context("a prefix length", verify(map(number, |length| length as u8),
+            |length| *length as u32 <= bits))),
+        preceded(keyword("mask"), context("a contiguous mask", map_res(address, |mask| mask_length(network, mask)))),
+    )))(input)?;
+    Ok((input, (network, prefix_length.unwrap_or(bits as u8))))
+}
+
+fn mask_length(network: IpAddr, mask: IpAddr) -> Result<u8, ()> {
+    let (mask, bits) = match (network, mask) {
+        (IpAddr::V4(_), IpAddr::V4(mask)) => { ((u32::from(mask) as u128) << 96, 32) }
+        (IpAddr::V6(_), IpAddr::V6(mask)) => { (u128::from(mask), 128) }
+        _ => { return Err(()); }
+    };
+    let length = mask.leading_ones();
+    match mask.checked_shl(length).unwrap_or(0) {
+        0 if length <= bits => { Ok(length as u8) }
+        _ => { Err(()) }
+    }
+}
+
+fn comparison(input: &str) -> FilterResult<'_, Expr> {
+    map(tuple((arith, relation, cut(context("an arithmetic expression", arith)))),
+        |(left, relation, right)| Expr::Compare(left, relation, right))(input)
+}
+
+fn relation(input: &str) -> FilterResult<'_, Relation> {
+    ws(alt((
+        value(Relation::Equal, tag("==")),
+        value(Relation::NotEqual, tag("!=")),
+        value(Relation::LessOrEqual, tag("<=")),
+        value(Relation::GreaterOrEqual, tag(">=")),
+        value(Relation::Equal, tag("=")),
+        value(Relation::Less, terminated(tag("<"), not(char('<')))),
+        value(Relation::Greater, terminated(tag(">"), not(char('>')))),
+    )))(input)
+}
+
+/// Arithmetic with the operator precedence of C, from `|` binding loosest to `*` and `/` binding tightest.
+fn arith(input: &str) -> FilterResult<'_, Arith> {
+    binary(input, arith_and, |input| value(ArithOp::Or, symbol_not_followed_by("|", '|'))(input))
+}
+
+fn arith_and(input: &str) -> FilterResult<'_, Arith> {
+    binary(input, arith_shift, |input| value(ArithOp::And, symbol_not_followed_by("&", '&'))(input))
+}
+
+fn arith_shift(input: &str) -> FilterResult<'_, Arith> {
+    binary(input, arith_sum, |input| alt((
+        value(ArithOp::ShiftLeft, symbol("<<")),
+        value(ArithOp::ShiftRight, symbol(">>")),
+    ))(input))
+}
+
+fn arith_sum(input: &str) -> FilterResult<'_, Arith> {
+    binary(input, arith_product, |input| alt((
+        value(ArithOp::Add, symbol("+")),
+        value(ArithOp::Subtract, symbol("-")),
+    ))(input))
+}
+
+fn arith_product(input: &str) -> FilterResult<'_, Arith> {
+    binary(input, operand, |input| alt((
+        value(ArithOp::Multiply, symbol("*")),
+        value(ArithOp::Divide, symbol("/")),
+    ))(input))
+}
+
+fn binary<'a>(input: &'a str,
+              operand: fn(&'a str) -> FilterResult<'a, Arith>,
+              operator: fn(&'a str) -> FilterResult<'a, ArithOp>) -> FilterResult<'a, Arith> {
+    let (input, first) = operand(input)?;
+    fold_many0(
+        pair(operator, operand),
+        move || first.clone(),
+        |left, (op, right)| Arith::Binary(Box::new(left), op, Box::new(right)),
+    )(input)
+}
+
+fn operand(input: &str) -> FilterResult<'_, Arith> {
+    alt((
+        map(number, Arith::Number),
+        value(Arith::Length, keyword("len")),
+        load,
+        delimited(symbol("("), arith, symbol(")")),
+    ))(input)
+}
+
+/// A load of 1, 2 or 4 bytes at an offset from the start of a layer, such as `ip[2:2]`.
+fn load(input: &str) -> FilterResult<'_, Arith> {
+    let (input, layer) = alt((
+        value(Layer::Link, keyword("ether")),
+        value(Layer::Ip6, keyword("ip6")),
+        value(Layer::Ip, keyword("ip")),
+        value(Layer::Tcp, keyword("tcp")),
+        value(Layer::Udp, keyword("udp")),
+        value(Layer::Icmp, keyword("icmp")),
+    ))(input)?;
+    let (input, (offset, size)) = delimited(
+        symbol("["),
+        cut(pair(arith, opt(preceded(symbol(":"), context("a size of 1, 2 or 4",
+            verify(map(number, |size| size as u8), |size| matches!(size, 1 | 2 | 4))))))),
+        cut(context("`]`", symbol("]"))),
+    )(input)?;
+    Ok((input, Arith::Load { layer, offset: Box::new(offset), size: size.unwrap_or(1) }))
+}
+
+/// A decimal or hexadecimal number.
+fn number(input: &str) -> FilterResult<'_, u32> {
+    ws(alt((
+        map_res(preceded(alt((tag("0x"), tag("0X"))), hex_digit1), |hex| u32::from_str_radix(hex, 16)),
+        map_res(terminated(digit1, not(peek(satisfy(|c: char| c.is_ascii_alphanumeric())))),
+            str::parse::<u32>),
+    )))(input)
+}
+
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> FilterResult<'a, &'a str> {
+    ws(terminated(tag(word), not(peek(satisfy(|c: char| c.is_ascii_alphanumeric() || c == '_')))))
+}
+
+fn symbol<'a>(symbol: &'static str) -> impl FnMut(&'a str) -> FilterResult<'a, &'a str> {
+    ws(tag(symbol))
+}
+
+fn symbol_not_followed_by<'a>(symbol: &'static str, next: char) -> impl FnMut(&'a str) -> FilterResult<'a, &'a str> {
+    ws(terminated(tag(symbol), not(char(next))))
+}
+
+fn ws<'a, T>(inner: impl FnMut(&'a str) -> FilterResult<'a, T>) -> impl FnMut(&'a str) -> FilterResult<'a, T> {
+    delimited(multispace0, inner, multispace0)
+}
@@ -1,19 +1,57 @@
 pub(crate) mod constants;
+pub(crate) mod decode;
+pub(crate) mod error;
+pub(crate) mod filter;
 pub(crate) mod pcap;
 pub(crate) mod pcapng;
+pub(crate) mod pcap_writer;
+pub(crate) mod pcapng_writer;
+pub(crate) mod reader;
+pub(crate) mod timestamp;
 
 pub use pcap::Pcap;
+pub use pcap::PcapFileHeader;
 pub use pcap::PcapPacketRecord;
+pub use pcap::PcapPacketRef;
 pub use pcap::PcapMagicNumber;
+pub use pcap_writer::PcapWriter;
+pub use reader::{PcapReader, PcapSlice, PcapPacketRefs, MappedPcap};
 pub use pcapng::PcapNG;
+pub use pcapng_writer::PcapNgWriter;
+pub use pcapng::{Section, Block, BlockOption, PcapNgPacket};
+pub use pcapng::{SectionHeaderBlock, InterfaceDescriptionBlock, EnhancedPacketBlock, SimplePacketBlock};
+pub use pcapng::{NameResolutionBlock, NameResolutionRecord, InterfaceStatisticsBlock};
+pub use timestamp::TimestampResolution;
+pub use error::{ParseError, ParseErrorReason};
+pub use decode::{decode_packet, DecodedPacket, DecodeError, LinkHeader};
+pub use decode::ethernet::{EthernetHeader, VlanTag};
+pub use decode::link::LinuxCookedHeader;
+pub use decode::ip::{IpPacket, Ipv4Packet, Ipv6Packet, IpFragment};
+pub use decode::reassembly::{FragmentReassembler, ReassembledPacket, IncompleteDatagram};
+pub use decode::udp::UdpDatagram;
+pub use filter::{BpfFilter, FilterError};
 pub use constants::{ETHERNET_HEADER_LENGTH_BYTES, IP_HEADER_LENGTH_BYTES, UDP_HEADER_LENGTH_BYTES};
+pub use constants::{LINKTYPE_NULL, LINKTYPE_ETHERNET, LINKTYPE_RAW, LINKTYPE_LOOP, LINKTYPE_LINUX_SLL, LINKTYPE_IPV4,
+    LINKTYPE_IPV6, LINKTYPE_LINUX_SLL2};
+pub use constants::{PCAP_FILE_HEADER_LENGTH, PCAP_RECORD_HEADER_LENGTH};
+pub use constants::IP_PROTOCOL_UDP;
+pub use nom::number::Endianness;
 
 use thiserror::Error;
 
 #[derive(Clone, Debug, Error)]
 pub enum PcapError {
-    #[error("Error parsing .pcap file")]
-    ParsePcapError,
-    #[error("Error parsing .pcapng file")]
-    ParsePcapNgError,
+    #[error("Error parsing .pcap file: {0}")]
+    ParsePcapError(ParseError),
+    #[error("Error parsing .pcapng file: {0}")]
+    ParsePcapNgError(ParseError),
+}
+
+impl PcapError {
+    /// The location and reason of the error.
+    pub fn parse_error(&self) -> &ParseError {
+        match self {
+            PcapError::ParsePcapError(err) | PcapError::ParsePcapNgError(err) => { err }
+        }
+    }
 }
@@ -1,22 +1,26 @@
 use std::fs::File;
 use std::io::{BufReader, Read};
+use std::time::Duration;
 use nom::bytes::complete::take;
 use nom::combinator::peek;
-use nom::IResult;
-use nom::multi::many1;
+use nom::error::context;
 use nom::number::complete::{le_u32, u16, u32};
 use nom::number::Endianness;
-use crate::{PcapError};
+use crate::constants::PCAP_RECORD_HEADER_LENGTH;
+use crate::error::{parse_error, NomError, ParseError, ParseErrorReason, ParseResult};
+use crate::{PcapError, PcapSlice};
+use crate::timestamp::TimestampResolution;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Pcap {
     pub header: PcapFileHeader,
     pub packets: Vec<PcapPacketRecord>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct PcapFileHeader {
-    pub magic_number: PcapMagicNumber,
+    pub endianness: Endianness,
+    pub ts_resolution: TimestampResolution,
     pub major_version: u16,
     pub minor_version: u16,
     pub snap_len: u32,
@@ -25,22 +29,77 @@ pub struct PcapFileHeader {
     pub f_bit: bool,
 }
 
-#[derive(Debug)]
+/// The magic number at the start of a .pcap file, which determines both the byte order of the file
+/// and the resolution of the packet timestamps.
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum PcapMagicNumber {
     LeMicros,     // 0xA1B2C3D4 - Little Endian - time fraction in micro seconds
+    BeMicros,     // 0xA1B2C3D4 - Big Endian - time fraction in micro seconds
+    LeNanos,      // 0xA1B23C4D - Little Endian - time fraction in nano seconds
     BeNanos,      // 0xA1B23C4D - Big Endian - time fraction in nano seconds
 }
 
-impl From<u32> for PcapMagicNumber {
-    fn from(value: u32) -> Self {
+const MAGIC_NUMBER_MICROS: u32 = 0xA1B2C3D4;
+const MAGIC_NUMBER_NANOS: u32 = 0xA1B23C4D;
+
+impl PcapMagicNumber {
+    pub fn new(endianness: Endianness, ts_resolution: TimestampResolution) -> Option<Self> {
+        let big_endian = match endianness {
+            Endianness::Big => { true }
+            Endianness::Little => { false }
+            Endianness::Native => { cfg!(target_endian = "big") }
+        };
+        match (big_endian, ts_resolution) {
+            (false, TimestampResolution::MICROS) => { Some(PcapMagicNumber::LeMicros) }
+            (true, TimestampResolution::MICROS) => { Some(PcapMagicNumber::BeMicros) }
+            (false, TimestampResolution::NANOS) => { Some(PcapMagicNumber::LeNanos) }
+            (true, TimestampResolution::NANOS) => { Some(PcapMagicNumber::BeNanos) }
+            _ => { None }
+        }
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        match self {
+            PcapMagicNumber::LeMicros | PcapMagicNumber::LeNanos => { Endianness::Little }
+            PcapMagicNumber::BeMicros | PcapMagicNumber::BeNanos => { Endianness::Big }
+        }
+    }
+
+    pub fn ts_resolution(&self) -> TimestampResolution {
+        match self {
+            PcapMagicNumber::LeMicros | PcapMagicNumber::BeMicros => { TimestampResolution::MICROS }
+            PcapMagicNumber::LeNanos | PcapMagicNumber::BeNanos => { TimestampResolution::NANOS }
+        }
+    }
+
+    /// The value of the magic number, to be written in the byte order of the file.
+    pub fn value(&self) -> u32 {
+        match self.ts_resolution() {
+            TimestampResolution::NANOS => { MAGIC_NUMBER_NANOS }
+            _ => { MAGIC_NUMBER_MICROS }
+        }
+    }
+}
+
+/// Decodes the first four bytes of a file, read as a little-endian value.
+impl TryFrom<u32> for PcapMagicNumber {
+    type Error = PcapError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
         match value {
-            0xA1B23C4D => PcapMagicNumber::BeNanos,
-            0xA1B2C3D4 | _ => PcapMagicNumber::LeMicros,
+            MAGIC_NUMBER_MICROS => { Ok(PcapMagicNumber::LeMicros) }
+            MAGIC_NUMBER_NANOS => { Ok(PcapMagicNumber::LeNanos) }
+            _ if value.swap_bytes() == MAGIC_NUMBER_MICROS => { Ok(PcapMagicNumber::BeMicros) }
+            _ if value.swap_bytes() == MAGIC_NUMBER_NANOS => { Ok(PcapMagicNumber::BeNanos) }
+            _ => {
+                let err = ParseError::new(0, ParseErrorReason::UnknownMagicNumber(value)).with_field("magic number");
+                Err(PcapError::ParsePcapError(err))
+            }
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct PcapPacketRecord {
     pub ts_secs: u32,
     pub ts_secs_fraction: u32,
@@ -49,19 +108,65 @@ pub struct PcapPacketRecord {
     pub packet_data: Vec<u8>,
 }
 
+/// A packet record borrowing its data from the underlying buffer, such as a memory-mapped file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PcapPacketRef<'a> {
+    pub ts_secs: u32,
+    pub ts_secs_fraction: u32,
+    pub captured_packet_length: u32,
+    pub original_packet_length: u32,
+    pub packet_data: &'a [u8],
+}
+
+impl PcapPacketRef<'_> {
+    pub fn to_record(&self) -> PcapPacketRecord {
+        PcapPacketRecord {
+            ts_secs: self.ts_secs,
+            ts_secs_fraction: self.ts_secs_fraction,
+            captured_packet_length: self.captured_packet_length,
+            original_packet_length: self.original_packet_length,
+            packet_data: self.packet_data.to_vec(),
+        }
+    }
+}
+
+impl PcapFileHeader {
+    /// Creates the header for a new little-endian, microsecond resolution, version 2.4 file.
+    pub fn new(link_type: u32, snap_len: u32) -> Self {
+        Self {
+            endianness: Endianness::Little,
+            ts_resolution: TimestampResolution::MICROS,
+            major_version: 2,
+            minor_version: 4,
+            snap_len,
+            link_type,
+            frame_cyclic_sequence: 0,
+            f_bit: false,
+        }
+    }
+
+    /// The magic number matching the byte order and timestamp resolution of the header, if any.
+    pub fn magic_number(&self) -> Option<PcapMagicNumber> {
+        PcapMagicNumber::new(self.endianness, self.ts_resolution)
+    }
+
+    /// Converts the timestamp of a packet record in this file to a `Duration` since the epoch.
+    pub fn timestamp(&self, ts_secs: u32, ts_secs_fraction: u32) -> Duration {
+        Duration::from_secs(ts_secs as u64) + self.ts_resolution.to_duration(ts_secs_fraction as u64)
+    }
+}
+
 impl TryFrom<File> for Pcap {
     type Error = PcapError;
 
     fn try_from(file: File) -> Result<Self, Self::Error> {
         let mut buf = Vec::<u8>::new();
         let mut reader = BufReader::new(file);
-        let _read = reader.read_to_end(&mut buf);
+        reader.read_to_end(&mut buf).map_err(|err| {
+            PcapError::ParsePcapError(ParseError::new(buf.len(), ParseErrorReason::Io(err.to_string())))
+        })?;
 
-        match parse_pcap_file(&buf) {
-            Ok((_input, pcap)) => { Ok(pcap) }
-            Err(_err) => {
-                Err(PcapError::ParsePcapError) }
-        }
+        parse_pcap_file(&buf)
     }
 }
 
@@ -69,41 +174,70 @@ impl TryFrom<&[u8]> for Pcap {
     type Error = PcapError;
 
     fn try_from(buf: &[u8]) -> Result<Self, Self::Error> {
-        match parse_pcap_file(buf) {
-            Ok((_input, pcap)) => { Ok(pcap) }
-            Err(_err) => {
-                Err(PcapError::ParsePcapError) }
-        }
+        parse_pcap_file(buf)
     }
 }
 
-fn parse_pcap_file(input: &[u8]) -> IResult<&[u8], Pcap> {
-    let (input, magic_number_as_le) = peek(le_u32)(input)?;
-    let endianness = determine_endianness(magic_number_as_le);
+impl Pcap {
+    /// Parses a possibly damaged file, such as a recording cut off mid-record. Returns all complete packets
+    /// together with a description of each damaged region that was skipped; only the file header must be intact.
+    pub fn parse_lenient(buf: &[u8]) -> Result<(Pcap, Vec<ParseError>), PcapError> {
+        let slice = PcapSlice::try_from(buf)?;
+        let mut packets = Vec::new();
+        let mut damage = Vec::new();
+        for packet in slice.packets_lenient() {
+            match packet {
+                Ok(packet) => { packets.push(packet.to_record()); }
+                Err(err) => { damage.push(err.parse_error().clone()); }
+            }
+        }
+        Ok((Pcap {
+            header: slice.header().clone(),
+            packets,
+        }, damage))
+    }
+}
 
-    let (input, header) = pcap_header(endianness)(input)?;
-    let (input, packets) = many1(pcap_packet_record(endianness))(input)?;
-    Ok((input, Pcap {
-        header,
+fn parse_pcap_file(input: &[u8]) -> Result<Pcap, PcapError> {
+    let slice = PcapSlice::try_from(input)?;
+    let packets = slice.packets()
+        .map(|packet| packet.map(|packet| packet.to_record()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Pcap {
+        header: slice.header().clone(),
         packets,
-    }))
+    })
+}
+
+/// Parses the file header, after determining the byte order and timestamp resolution from the magic number.
+pub(crate) fn parse_pcap_header(input: &[u8]) -> Result<(&[u8], PcapFileHeader), PcapError> {
+    let (_, magic_number_as_le) = context("magic number", peek(le_u32))(input)
+        .map_err(|err| PcapError::ParsePcapError(parse_error(input, err)))?;
+    let magic_number = PcapMagicNumber::try_from(magic_number_as_le)?;
+
+    pcap_header(magic_number)(input).map_err(|err| PcapError::ParsePcapError(parse_error(input, err)))
 }
 
-fn pcap_header(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], PcapFileHeader> {
+fn pcap_header(magic_number: PcapMagicNumber) -> impl Fn(&[u8]) -> ParseResult<PcapFileHeader> {
     move |input| {
-        let (input, magic_number) = u32(endianness)(input)?;
-        let magic_number = PcapMagicNumber::from(magic_number);
-
-        let (input, major_version) = u16(endianness)(input)?;
-        let (input, minor_version) = u16(endianness)(input)?;
-        let (input, _reserved) = take(8usize)(input)?;
-        let (input, snap_len) = u32(endianness)(input)?;
-        let (input, link_type) = u32(endianness)(input)?;
+        let endianness = magic_number.endianness();
+        let (input, _magic_number) = context("magic number", u32(endianness))(input)?;
+
+        let (rest, major_version) = context("major version", u16(endianness))(input)?;
+        let (rest, minor_version) = context("minor version", u16(endianness))(rest)?;
+        if major_version != 2 {
+            let reason = ParseErrorReason::UnsupportedVersion { major: major_version, minor: minor_version };
+            return Err(NomError::failure(input, "major version", reason));
+        }
+        let (input, _reserved) = context("reserved fields", take(8usize))(rest)?;
+        let (input, snap_len) = context("snap length", u32(endianness))(input)?;
+        let (input, link_type) = context("link type", u32(endianness))(input)?;
         let frame_cyclic_sequence: u8 = ((link_type & 0xF0000000) >> 28) as u8;
-        let f_bit = if (link_type & 0x10000000) >> 27 == 0 { false } else { true };
+        let f_bit = link_type & 0x10000000 != 0;
 
         Ok((input, PcapFileHeader {
-            magic_number,
+            endianness,
+            ts_resolution: magic_number.ts_resolution(),
             major_version,
             minor_version,
             snap_len,
@@ -114,34 +248,134 @@ fn pcap_header(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], PcapF
     }
 }
 
-fn pcap_packet_record(endianness: Endianness) -> impl Fn(&[u8]) -> IResult<&[u8], PcapPacketRecord> {
+/// The fixed-size part of a packet record, preceding the packet data.
+pub(crate) struct PcapRecordHeader {
+    pub ts_secs: u32,
+    pub ts_secs_fraction: u32,
+    pub captured_packet_length: u32,
+    pub original_packet_length: u32,
+}
+
+/// Parses a record header, rejecting a captured length greater than `snap_len` unless that is 0.
+pub(crate) fn pcap_record_header(endianness: Endianness, snap_len: u32) -> impl Fn(&[u8]) -> ParseResult<PcapRecordHeader> {
     move |input| {
-        let (input, ts_secs) = u32(endianness)(input)?;
-        let (input, ts_secs_fraction) = u32(endianness)(input)?;
-        let (input, captured_packet_length) = u32(endianness)(input)?;
-        let (input, original_packet_length) = u32(endianness)(input)?;
-        // TODO also parse the Ethernet and IP headers if we need to support other data link and network protocols
-        // let (input, _ethernet_header) = take(ETHERNET_HEADER_LENGTH)(input)?;
-        // let (input, _ip_header) = take(IP_HEADER_LENGTH)(input)?;
-        // TODO also parse the UDP header if needed to know what the original ports and addresses were.
-        // let (input, _udp_header) = take(UDP_HEADER_LENGTH)(input)?;
-        let (input, packet_data) = take(captured_packet_length)(input)?;
-
-        let packet_data = packet_data.to_vec();
-        Ok((input, PcapPacketRecord {
+        let (input, ts_secs) = context("timestamp seconds", u32(endianness))(input)?;
+        let (input, ts_secs_fraction) = context("timestamp fraction", u32(endianness))(input)?;
+        let (rest, captured_packet_length) = context("captured length", u32(endianness))(input)?;
+        if snap_len != 0 && captured_packet_length > snap_len {
+            let reason = ParseErrorReason::CapturedLengthExceedsSnapLen { captured_length: captured_packet_length, snap_len };
+            return Err(NomError::failure(input, "captured length", reason));
+        }
+        let (input, original_packet_length) = context("original length", u32(endianness))(rest)?;
+        Ok((input, PcapRecordHeader {
             ts_secs,
             ts_secs_fraction,
             captured_packet_length,
             original_packet_length,
+        }))
+    }
+}
+
+/// The largest packet libpcap captures, to judge record headers in files without a snap length.
+const MAX_PLAUSIBLE_PACKET_LENGTH: u32 = 262144;
+
+/// Finds the next record after a damaged record at the start of `input`, for lenient parsing.
+/// A position is accepted when it holds a plausible record header that is followed by either the end
+/// of the input or another plausible record header.
+pub(crate) fn resync_pcap_record<'a>(header: &PcapFileHeader, input: &'a [u8]) -> Option<&'a [u8]> {
+    (1..input.len()).map(|skip| &input[skip..]).find(|candidate| {
+        match plausible_record_length(header, candidate) {
+            Some(length) => {
+                let next = &candidate[length..];
+                next.is_empty() || plausible_record_length(header, next).is_some()
+            }
+            None => { false }
+        }
+    })
+}
+
+/// The total length of the record at the start of `input`, if its header looks valid and its data is complete.
+fn plausible_record_length(header: &PcapFileHeader, input: &[u8]) -> Option<usize> {
+    let (rest, record) = pcap_record_header(header.endianness, header.snap_len)(input).ok()?;
+    let plausible = record.original_packet_length > 0
+        && record.original_packet_length <= MAX_PLAUSIBLE_PACKET_LENGTH
+        && record.captured_packet_length <= record.original_packet_length
+        && (record.ts_secs_fraction as u128) < header.ts_resolution.ticks_per_second()
+        && record.captured_packet_length as usize <= rest.len();
+    plausible.then_some(PCAP_RECORD_HEADER_LENGTH + record.captured_packet_length as usize)
+}
+
+pub(crate) fn pcap_packet_record(endianness: Endianness, snap_len: u32) -> impl Fn(&[u8]) -> ParseResult<PcapPacketRef<'_>> {
+    move |input| {
+        let (input, record_header) = pcap_record_header(endianness, snap_len)(input)?;
+        // The packet data is decoded on demand, see `decode_packet`.
+        let (input, packet_data) = context("packet data", take(record_header.captured_packet_length))(input)?;
+
+        Ok((input, PcapPacketRef {
+            ts_secs: record_header.ts_secs,
+            ts_secs_fraction: record_header.ts_secs_fraction,
+            captured_packet_length: record_header.captured_packet_length,
+            original_packet_length: record_header.original_packet_length,
             packet_data,
         }))
     }
 }
 
-fn determine_endianness(magic_number: u32) -> Endianness {
-    if magic_number == 0xA1B2C3D4 {
-        Endianness::Little
-    } else { // magic_number == 0xA1B23C4D
-        Endianness::Big
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_all_magic_numbers() {
+        let cases = [
+            ([0xD4, 0xC3, 0xB2, 0xA1], Endianness::Little, TimestampResolution::MICROS),
+            ([0xA1, 0xB2, 0xC3, 0xD4], Endianness::Big, TimestampResolution::MICROS),
+            ([0x4D, 0x3C, 0xB2, 0xA1], Endianness::Little, TimestampResolution::NANOS),
+            ([0xA1, 0xB2, 0x3C, 0x4D], Endianness::Big, TimestampResolution::NANOS),
+        ];
+        for (magic, endianness, ts_resolution) in cases {
+            let magic_number = PcapMagicNumber::try_from(u32::from_le_bytes(magic)).unwrap();
+            assert_eq!(magic_number.endianness(), endianness);
+            assert_eq!(magic_number.ts_resolution(), ts_resolution);
+            assert_eq!(PcapMagicNumber::new(endianness, ts_resolution), Some(magic_number));
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn reject_unknown_magic_number() {
+        let mut buf = vec![0x0A, 0x0D, 0x0D, 0x0A];
+        buf.resize(40, 0);
+        let err = Pcap::try_from(buf.as_slice()).unwrap_err();
+        assert_eq!(err.parse_error().reason, ParseErrorReason::UnknownMagicNumber(0x0A0D0D0A));
+        assert_eq!(err.parse_error().offset, 0);
+    }
+
+    #[test]
+    fn locate_invalid_header_and_record() {
+        let mut buf = Vec::new();
+        for value in [0xA1B2C3D4u32, 0x00040002, 0, 0, 4, 1, 0, 0, 5, 5] {
+            buf.extend_from_slice(&value.to_le_bytes());
+        }
+        buf.extend_from_slice(&[0; 5]);
+        let err = Pcap::try_from(buf.as_slice()).unwrap_err();
+        let expected = ParseError::new(32, ParseErrorReason::CapturedLengthExceedsSnapLen { captured_length: 5, snap_len: 4 })
+            .with_packet_index(0)
+            .with_field("captured length");
+        assert_eq!(err.parse_error(), &expected);
+        assert_eq!(err.to_string(), "Error parsing .pcap file: captured length 5 exceeds the snap length 4 \
+            at byte offset 32, parsing the captured length of packet 1");
+
+        buf[4] = 3;
+        let err = Pcap::try_from(buf.as_slice()).unwrap_err();
+        assert_eq!(err.parse_error().reason, ParseErrorReason::UnsupportedVersion { major: 3, minor: 4 });
+        assert_eq!(err.parse_error().offset, 4);
+    }
+
+    #[test]
+    fn timestamp_follows_resolution() {
+        let mut header = PcapFileHeader::new(1, 65535);
+        assert_eq!(header.timestamp(10, 250_000), Duration::new(10, 250_000_000));
+        header.ts_resolution = TimestampResolution::NANOS;
+        assert_eq!(header.timestamp(10, 250_000), Duration::new(10, 250_000));
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::{Error, ErrorKind, Write};
+use nom::number::Endianness;
+use crate::pcap::{Pcap, PcapFileHeader, PcapPacketRecord};
+
+/// Writes .pcap files to any `Write`: the global header on creation, followed by the packet records one at a time.
+/// Multi-byte fields are written in the byte order given by the `endianness` of the header.
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    endianness: Endianness,
+}
+
+impl<W: Write> PcapWriter<W> {
+    pub fn new(mut writer: W, header: &PcapFileHeader) -> Result<Self, Error> {
+        let Some(magic_number) = header.magic_number() else {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                format!("Timestamp resolution {:?} cannot be expressed in a .pcap file", header.ts_resolution)));
+        };
+        let endianness = header.endianness;
+        write_u32(&mut writer, endianness, magic_number.value())?;
+        write_u16(&mut writer, endianness, header.major_version)?;
+        write_u16(&mut writer, endianness, header.minor_version)?;
+        writer.write_all(&[0u8; 8])?; // reserved
+        write_u32(&mut writer, endianness, header.snap_len)?;
+        write_u32(&mut writer, endianness, header.link_type)?;
+
+        Ok(Self {
+            writer,
+            endianness,
+        })
+    }
+
+    pub fn write_packet(&mut self, packet: &PcapPacketRecord) -> Result<(), Error> {
+        if packet.captured_packet_length as usize != packet.packet_data.len() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                format!("Captured packet length {} does not match the length of the packet data {}",
+                        packet.captured_packet_length, packet.packet_data.len())));
+        }
+        write_u32(&mut self.writer, self.endianness, packet.ts_secs)?;
+        write_u32(&mut self.writer, self.endianness, packet.ts_secs_fraction)?;
+        write_u32(&mut self.writer, self.endianness, packet.captured_packet_length)?;
+        write_u32(&mut self.writer, self.endianness, packet.original_packet_length)?;
+        self.writer.write_all(&packet.packet_data)
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl Pcap {
+    /// Serializes the complete file, header and packets, to `writer`.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<W, Error> {
+        let mut pcap_writer = PcapWriter::new(writer, &self.header)?;
+        for packet in &self.packets {
+            pcap_writer.write_packet(packet)?;
+        }
+        pcap_writer.flush()?;
+        Ok(pcap_writer.into_inner())
+    }
+}
+
+pub(crate) fn write_u16<W: Write>(writer: &mut W, endianness: Endianness, value: u16) -> Result<(), Error> {
+    match endianness {
+        Endianness::Big => { writer.write_all(&value.to_be_bytes()) }
+        Endianness::Little => { writer.write_all(&value.to_le_bytes()) }
+        Endianness::Native => { writer.write_all(&value.to_ne_bytes()) }
+    }
+}
+
+pub(crate) fn write_u32<W: Write>(writer: &mut W, endianness: Endianness, value: u32) -> Result<(), Error> {
+    match endianness {
+        Endianness::Big => { writer.write_all(&value.to_be_bytes()) }
+        Endianness::Little => { writer.write_all(&value.to_le_bytes()) }
+        Endianness::Native => { writer.write_all(&value.to_ne_bytes()) }
+    }
+}
+
+pub(crate) fn write_u64<W: Write>(writer: &mut W, endianness: Endianness, value: u64) -> Result<(), Error> {
+    match endianness {
+        Endianness::Big => { writer.write_all(&value.to_be_bytes()) }
+        Endianness::Little => { writer.write_all(&value.to_le_bytes()) }
+        Endianness::Native => { writer.write_all(&value.to_ne_bytes()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(header: &[u8; 24], endianness: Endianness) -> Vec<u8> {
+        let mut buf = header.to_vec();
+        for (ts_secs, data) in [(1u32, vec![1u8, 2, 3]), (2u32, vec![4u8, 5, 6, 7])] {
+            for value in [ts_secs, 500, data.len() as u32, data.len() as u32 + 10] {
+                write_u32(&mut buf, endianness, value).unwrap();
+            }
+            buf.extend_from_slice(&data);
+        }
+        buf
+    }
+
+    #[test]
+    fn round_trip_little_endian_micros() {
+        let header = [0xD4, 0xC3, 0xB2, 0xA1, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0, 0, 1, 0, 0, 0];
+        let bytes = capture(&header, Endianness::Little);
+
+        let pcap = Pcap::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(pcap.header.endianness, Endianness::Little);
+        assert_eq!(pcap.packets[1].packet_data, vec![4, 5, 6, 7]);
+        assert_eq!(pcap.write_to(Vec::new()).unwrap(), bytes);
+    }
+
+    #[test]
+    fn round_trip_big_endian_nanos() {
+        let header = [0xA1, 0xB2, 0x3C, 0x4D, 0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0, 0, 0, 1];
+        let bytes = capture(&header, Endianness::Big);
+
+        let pcap = Pcap::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(pcap.header.endianness, Endianness::Big);
+        assert_eq!(pcap.header.snap_len, 0xFFFF);
+        assert_eq!(pcap.write_to(Vec::new()).unwrap(), bytes);
+    }
+
+    #[test]
+    fn reject_inconsistent_packet_length() {
+        let mut writer = PcapWriter::new(Vec::new(), &PcapFileHeader::new(1, 65535)).unwrap();
+        let packet = PcapPacketRecord {
+            ts_secs: 0,
+            ts_secs_fraction: 0,
+            captured_packet_length: 4,
+            original_packet_length: 4,
+            packet_data: vec![1, 2],
+        };
+        assert!(writer.write_packet(&packet).is_err());
+    }
+}
@@ -1,11 +1,262 @@
 use std::fs::File;
 use std::io::{BufReader, Read};
-use nom::IResult;
-use crate::PcapError;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use memmap2::Mmap;
+use nom::bytes::complete::take;
+use nom::combinator::peek;
+use nom::error::context;
+use nom::number::complete::{i64, le_u32, u16, u32, u64};
+use nom::number::Endianness;
+use crate::constants::*;
+use crate::error::{in_packet, parse_error, NomError, ParseResult};
+use crate::{ParseError, ParseErrorReason, PcapError};
+use crate::timestamp::TimestampResolution;
 
 #[derive(Debug)]
 pub struct PcapNG {
-    pub placeholder: usize,
+    pub sections: Vec<Section>,
+}
+
+/// A section starts with a Section Header Block and contains all blocks up to the next section.
+/// Interface ids used by packet and statistics blocks are indices into `interfaces`.
+#[derive(Debug)]
+pub struct Section {
+    pub header: SectionHeaderBlock,
+    pub interfaces: Vec<InterfaceDescriptionBlock>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug)]
+pub enum Block {
+    EnhancedPacket(EnhancedPacketBlock),
+    SimplePacket(SimplePacketBlock),
+    NameResolution(NameResolutionBlock),
+    InterfaceStatistics(InterfaceStatisticsBlock),
+    Unknown { block_type: u32, body: Vec<u8> },
+}
+
+/// A generic block option; the typed fields of each block hold the decoded values of the known options.
+#[derive(Clone, Debug)]
+pub struct BlockOption {
+    pub code: u16,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct SectionHeaderBlock {
+    pub endianness: Endianness,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub section_length: i64,    // -1 when the length is not specified
+    pub comment: Option<String>,
+    pub hardware: Option<String>,
+    pub os: Option<String>,
+    pub user_application: Option<String>,
+    pub options: Vec<BlockOption>,
+}
+
+#[derive(Debug)]
+pub struct InterfaceDescriptionBlock {
+    pub link_type: u16,
+    pub snap_len: u32,          // 0 when there is no limit
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub ts_resolution: TimestampResolution,
+    pub ts_offset: i64,         // seconds to add to all timestamps of this interface
+    pub options: Vec<BlockOption>,
+}
+
+#[derive(Debug)]
+pub struct EnhancedPacketBlock {
+    pub interface_id: u32,
+    pub timestamp: u64,         // expressed in the `ts_resolution` of the interface
+    pub captured_packet_length: u32,
+    pub original_packet_length: u32,
+    pub packet_data: Vec<u8>,
+    pub comment: Option<String>,
+    pub flags: Option<u32>,
+    pub options: Vec<BlockOption>,
+}
+
+/// Simple Packet Blocks belong to the first interface of the section and carry no timestamp.
+#[derive(Debug)]
+pub struct SimplePacketBlock {
+    pub original_packet_length: u32,
+    pub packet_data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct NameResolutionBlock {
+    pub records: Vec<NameResolutionRecord>,
+    pub options: Vec<BlockOption>,
+}
+
+#[derive(Debug)]
+pub enum NameResolutionRecord {
+    Ipv4 { address: Ipv4Addr, names: Vec<String> },
+    Ipv6 { address: Ipv6Addr, names: Vec<String> },
+    Unknown { record_type: u16, value: Vec<u8> },
+}
+
+#[derive(Debug)]
+pub struct InterfaceStatisticsBlock {
+    pub interface_id: u32,
+    pub timestamp: u64,
+    pub comment: Option<String>,
+    pub received: Option<u64>,
+    pub dropped: Option<u64>,
+    pub options: Vec<BlockOption>,
+}
+
+/// A captured packet, from either an Enhanced or a Simple Packet Block, together with the interface it was captured on.
+#[derive(Debug)]
+pub struct PcapNgPacket<'a> {
+    pub interface_id: u32,
+    pub interface: &'a InterfaceDescriptionBlock,
+    pub timestamp: Option<Duration>,
+    pub captured_packet_length: u32,
+    pub original_packet_length: u32,
+    pub packet_data: &'a [u8],
+}
+
+impl PcapNG {
+    /// Parses a possibly damaged file, such as a recording cut off mid-block. Returns all intact blocks
+    /// together with a description of each damaged region that was skipped.
+    pub fn parse_lenient(buf: &[u8]) -> Result<(PcapNG, Vec<ParseError>), PcapError> {
+        parse_pcapng_file(buf, true).map_err(PcapError::ParsePcapNgError)
+    }
+
+    /// Parses a possibly damaged file like `parse_lenient`, from a memory map of the file instead of a copy of it,
+    /// so that only the parsed blocks take up memory.
+    pub fn parse_file_lenient(file: &File) -> Result<(PcapNG, Vec<ParseError>), PcapError> {
+        // Safety: the mapping is read-only and only lives while parsing; as for `MappedPcap`, modifying or
+        // truncating the file meanwhile is undefined behaviour, which is accepted for recordings.
+        let mmap = unsafe { Mmap::map(file) }
+            .map_err(|err| PcapError::ParsePcapNgError(ParseError::new(0, ParseErrorReason::Io(err.to_string()))))?;
+        PcapNG::parse_lenient(&mmap)
+    }
+
+    /// Iterates all packets in the file, across all sections.
+    pub fn packets(&self) -> impl Iterator<Item = PcapNgPacket<'_>> {
+        self.sections.iter().flat_map(Section::packets)
+    }
+}
+
+impl Section {
+    /// Iterates the packets of the section, skipping packets of interfaces the section does not describe.
+    /// Simple Packet Blocks belong to the first interface.
+    pub fn packets(&self) -> impl Iterator<Item = PcapNgPacket<'_>> {
+        self.blocks.iter().filter_map(move |block| {
+            match block {
+                Block::EnhancedPacket(epb) => {
+                    let interface = self.interfaces.get(epb.interface_id as usize)?;
+                    Some(PcapNgPacket {
+                        interface_id: epb.interface_id,
+                        interface,
+                        timestamp: Some(interface.timestamp(epb.timestamp)),
+                        captured_packet_length: epb.captured_packet_length,
+                        original_packet_length: epb.original_packet_length,
+                        packet_data: epb.packet_data.as_slice(),
+                    })
+                }
+                Block::SimplePacket(spb) => {
+                    Some(PcapNgPacket {
+                        interface_id: 0,
+                        interface: self.interfaces.first()?,
+                        timestamp: None,
+                        captured_packet_length: spb.packet_data.len() as u32,
+                        original_packet_length: spb.original_packet_length,
+                        packet_data: spb.packet_data.as_slice(),
+                    })
+                }
+                _ => { None }
+            }
+        })
+    }
+}
+
+impl SectionHeaderBlock {
+    /// Creates the header for a new little-endian, version 1.0 section of unspecified length.
+    pub fn new() -> Self {
+        Self {
+            endianness: Endianness::Little,
+            major_version: 1,
+            minor_version: 0,
+            section_length: -1,
+            comment: None,
+            hardware: None,
+            os: None,
+            user_application: None,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl Default for SectionHeaderBlock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InterfaceDescriptionBlock {
+    pub fn new(link_type: u16, snap_len: u32) -> Self {
+        Self {
+            link_type,
+            snap_len,
+            name: None,
+            description: None,
+            ts_resolution: TimestampResolution::default(),
+            ts_offset: 0,
+            options: Vec::new(),
+        }
+    }
+
+    /// Converts a raw timestamp of a packet captured on this interface to a `Duration` since the epoch,
+    /// applying both the `if_tsresol` and `if_tsoffset` of the interface.
+    pub fn timestamp(&self, ticks: u64) -> Duration {
+        let timestamp = self.ts_resolution.to_duration(ticks);
+        let offset = Duration::from_secs(self.ts_offset.unsigned_abs());
+        if self.ts_offset >= 0 {
+            timestamp.saturating_add(offset)
+        } else {
+            timestamp.saturating_sub(offset)
+        }
+    }
+
+    /// Converts a `Duration` since the epoch to a raw timestamp for a packet captured on this interface,
+    /// the inverse of `timestamp`.
+    pub fn ticks(&self, timestamp: Duration) -> u64 {
+        let offset = Duration::from_secs(self.ts_offset.unsigned_abs());
+        let timestamp = if self.ts_offset >= 0 {
+            timestamp.saturating_sub(offset)
+        } else {
+            timestamp.saturating_add(offset)
+        };
+        self.ts_resolution.to_ticks(timestamp)
+    }
+}
+
+impl EnhancedPacketBlock {
+    pub fn new(interface_id: u32, timestamp: u64, packet_data: Vec<u8>) -> Self {
+        Self {
+            interface_id,
+            timestamp,
+            captured_packet_length: packet_data.len() as u32,
+            original_packet_length: packet_data.len() as u32,
+            packet_data,
+            comment: None,
+            flags: None,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl BlockOption {
+    /// Interprets the option value as UTF-8 text, as used by `opt_comment` and the string options.
+    pub fn as_string(&self) -> String {
+        String::from_utf8_lossy(&self.value).trim_end_matches('\0').to_string()
+    }
 }
 
 impl TryFrom<File> for PcapNG {
@@ -14,13 +265,11 @@ impl TryFrom<File> for PcapNG {
     fn try_from(file: File) -> Result<Self, Self::Error> {
         let mut buf = Vec::<u8>::new();
         let mut reader = BufReader::new(file);
-        let _read = reader.read_to_end(&mut buf);
+        reader.read_to_end(&mut buf).map_err(|err| {
+            PcapError::ParsePcapNgError(ParseError::new(buf.len(), ParseErrorReason::Io(err.to_string())))
+        })?;
 
-        match parse_pcapng_file(&buf) {
-            Ok((_input, pcap)) => { Ok(pcap) }
-            Err(_err) => {
-                Err(PcapError::ParsePcapNgError) }
-        }
+        PcapNG::try_from(buf.as_slice())
     }
 }
 
@@ -28,14 +277,552 @@ impl TryFrom<&[u8]> for PcapNG {
     type Error = PcapError;
 
     fn try_from(buf: &[u8]) -> Result<Self, Self::Error> {
-        match parse_pcapng_file(buf) {
-            Ok((_input, pcap)) => { Ok(pcap) }
-            Err(_err) => {
-                Err(PcapError::ParsePcapNgError) }
+        parse_pcapng_file(buf, false)
+            .map(|(pcapng, _damage)| pcapng)
+            .map_err(PcapError::ParsePcapNgError)
+    }
+}
+
+/// Number of padding bytes needed to align a field of `length` bytes to 32 bits.
+pub(crate) fn padding_length(length: usize) -> usize {
+    (4 - length % 4) % 4
+}
+
+/// Parses all sections of a file. In lenient mode a damaged block is reported and skipped, resuming at the next
+/// block that looks valid, instead of failing the whole file; only the first section header must be intact.
+fn parse_pcapng_file(buf: &[u8], lenient: bool) -> Result<(PcapNG, Vec<ParseError>), ParseError> {
+    let mut input = buf;
+    let mut sections: Vec<Section> = Vec::new();
+    let mut damage = Vec::new();
+    let mut packet_index = 0;
+    // A file holds at least one section, so an empty file is reported as truncated.
+    while sections.is_empty() || !input.is_empty() {
+        let result = if sections.is_empty() || starts_section(input) {
+            section_header_block(input).map(|(rest, header)| {
+                sections.push(Section {
+                    header,
+                    interfaces: Vec::new(),
+                    blocks: Vec::new(),
+                });
+                rest
+            })
+        } else {
+            let section = sections.last_mut().expect("A section has been started.");
+            section_block(section, input, &mut packet_index).map(|(rest, ())| rest)
+        };
+        match result {
+            Ok(rest) => { input = rest; }
+            Err(err) => {
+                let err = parse_error(buf, err);
+                let endianness = match sections.last() {
+                    Some(section) if lenient => { section.header.endianness }
+                    _ => { return Err(err); }
+                };
+                damage.push(err);
+                match resync_block(endianness, input) {
+                    Some(rest) => { input = rest; }
+                    None => { break; }
+                }
+            }
         }
     }
+    Ok((PcapNG {
+        sections,
+    }, damage))
+}
+
+fn starts_section(input: &[u8]) -> bool {
+    matches!(le_u32::<_, NomError>(input), Ok((_, SECTION_HEADER_BLOCK)))
 }
 
-fn parse_pcapng_file(_input: &[u8]) -> IResult<&[u8], PcapNG> {
-    unimplemented!(".pcapng files not yet supported");
-}
\ No newline at end of file
+/// Parses the next block of `section`, counting its packets in `packet_index` so that errors can refer to
+/// the packet across sections.
+fn section_block<'a>(section: &mut Section, input: &'a [u8], packet_index: &mut usize) -> ParseResult<'a, ()> {
+    let endianness = section.header.endianness;
+    let (rest, (block_type, body)) = generic_block(endianness)(input)?;
+    let block = match block_type {
+        INTERFACE_DESCRIPTION_BLOCK => {
+            let (_, idb) = interface_description_block(endianness)(body)?;
+            section.interfaces.push(idb);
+            None
+        }
+        ENHANCED_PACKET_BLOCK => {
+            let (_, epb) = in_packet(*packet_index, enhanced_packet_block(endianness)(body))?;
+            let interface = match section.interfaces.get(epb.interface_id as usize) {
+                Some(interface) => { interface }
+                None => {
+                    let reason = ParseErrorReason::UnknownInterface(epb.interface_id);
+                    return in_packet(*packet_index, Err(NomError::failure(body, "interface id", reason)));
+                }
+            };
+            if interface.snap_len != 0 && epb.captured_packet_length > interface.snap_len {
+                let reason = ParseErrorReason::CapturedLengthExceedsSnapLen {
+                    captured_length: epb.captured_packet_length,
+                    snap_len: interface.snap_len,
+                };
+                return in_packet(*packet_index, Err(NomError::failure(&body[12..], "captured length", reason)));
+            }
+            *packet_index += 1;
+            Some(Block::EnhancedPacket(epb))
+        }
+        SIMPLE_PACKET_BLOCK => {
+            let snap_len = match section.interfaces.first() {
+                Some(interface) => { interface.snap_len }
+                None => {
+                    let reason = ParseErrorReason::UnknownInterface(0);
+                    return in_packet(*packet_index, Err(NomError::failure(input, "block type", reason)));
+                }
+            };
+            let (_, spb) = in_packet(*packet_index, simple_packet_block(endianness, snap_len)(body))?;
+            *packet_index += 1;
+            Some(Block::SimplePacket(spb))
+        }
+        NAME_RESOLUTION_BLOCK => {
+            let (_, nrb) = name_resolution_block(endianness)(body)?;
+            Some(Block::NameResolution(nrb))
+        }
+        INTERFACE_STATISTICS_BLOCK => {
+            let (_, isb) = interface_statistics_block(endianness)(body)?;
+            Some(Block::InterfaceStatistics(isb))
+        }
+        _ => {
+            Some(Block::Unknown { block_type, body: body.to_vec() })
+        }
+    };
+    if let Some(block) = block {
+        section.blocks.push(block);
+    }
+    Ok((rest, ()))
+}
+
+/// Finds the next block after a damaged block at the start of `input`. Blocks are aligned to 32 bits,
+/// so the candidates are the following 32-bit boundaries holding a known block type with a consistent
+/// leading and trailing block length.
+fn resync_block(endianness: Endianness, input: &[u8]) -> Option<&[u8]> {
+    (4..input.len()).step_by(4).map(|skip| &input[skip..]).find(|candidate| {
+        if starts_section(candidate) {
+            return section_header_block(candidate).is_ok();
+        }
+        match generic_block(endianness)(candidate) {
+            Ok((_, (block_type, _))) => { KNOWN_BLOCK_TYPES.contains(&block_type) }
+            Err(_) => { false }
+        }
+    })
+}
+
+/// Parses the framing shared by all blocks, returning the block type and the block body.
+fn generic_block(endianness: Endianness) -> impl Fn(&[u8]) -> ParseResult<(u32, &[u8])> {
+    move |input| {
+        let (rest, block_type) = context("block type", u32(endianness))(input)?;
+        let (body, block_total_length) = context("block length", u32(endianness))(rest)?;
+        if block_total_length < 12 || block_total_length % 4 != 0 {
+            let reason = ParseErrorReason::InvalidBlockLength(block_total_length);
+            return Err(NomError::failure(rest, "block length", reason));
+        }
+        let (trailer, body) = context("block body", take(block_total_length - 12))(body)?;
+        let (rest, trailing_length) = context("trailing block length", u32(endianness))(trailer)?;
+        if trailing_length != block_total_length {
+            let reason = ParseErrorReason::InvalidBlockLength(trailing_length);
+            return Err(NomError::failure(trailer, "trailing block length", reason));
+        }
+        Ok((rest, (block_type, body)))
+    }
+}
+
+fn section_header_block(input: &[u8]) -> ParseResult<'_, SectionHeaderBlock> {
+    // The byte-order magic follows the block type (a palindrome) and the block length.
+    let (_, header_start) = context("section header", peek(take(12usize)))(input)?;
+    let (_, block_type) = le_u32(header_start)?;
+    if block_type != SECTION_HEADER_BLOCK {
+        return Err(NomError::failure(input, "block type", ParseErrorReason::UnknownMagicNumber(block_type)));
+    }
+    let (_, byte_order_magic) = le_u32(&header_start[8..])?;
+    let endianness = if byte_order_magic == BYTE_ORDER_MAGIC {
+        Endianness::Little
+    } else if byte_order_magic == BYTE_ORDER_MAGIC.swap_bytes() {
+        Endianness::Big
+    } else {
+        let reason = ParseErrorReason::UnknownMagicNumber(byte_order_magic);
+        return Err(NomError::failure(&input[8..], "byte-order magic", reason));
+    };
+
+    let (input, (_block_type, body)) = generic_block(endianness)(input)?;
+    let (body, _byte_order_magic) = u32(endianness)(body)?;
+    let (rest, major_version) = context("major version", u16(endianness))(body)?;
+    let (rest, minor_version) = context("minor version", u16(endianness))(rest)?;
+    if major_version != 1 {
+        let reason = ParseErrorReason::UnsupportedVersion { major: major_version, minor: minor_version };
+        return Err(NomError::failure(body, "major version", reason));
+    }
+    let (body, section_length) = context("section length", i64(endianness))(rest)?;
+    let (_, options) = block_options(endianness)(body)?;
+
+    Ok((input, SectionHeaderBlock {
+        endianness,
+        major_version,
+        minor_version,
+        section_length,
+        comment: option_string(&options, OPT_COMMENT),
+        hardware: option_string(&options, SHB_HARDWARE),
+        os: option_string(&options, SHB_OS),
+        user_application: option_string(&options, SHB_USERAPPL),
+        options,
+    }))
+}
+
+fn interface_description_block(endianness: Endianness) -> impl Fn(&[u8]) -> ParseResult<InterfaceDescriptionBlock> {
+    move |input| {
+        let (input, link_type) = context("link type", u16(endianness))(input)?;
+        let (input, _reserved) = context("reserved field", u16(endianness))(input)?;
+        let (input, snap_len) = context("snap length", u32(endianness))(input)?;
+        let (input, options) = block_options(endianness)(input)?;
+
+        let ts_resolution = find_option(&options, IF_TSRESOL)
+            .and_then(|option| option.value.first())
+            .map(|value| TimestampResolution::from_if_tsresol(*value))
+            .unwrap_or_default();
+        let ts_offset = find_option(&options, IF_TSOFFSET)
+            .and_then(|option| i64::<_, nom::error::Error<&[u8]>>(endianness)(option.value.as_slice()).ok())
+            .map(|(_, offset)| offset)
+            .unwrap_or(0);
+
+        Ok((input, InterfaceDescriptionBlock {
+            link_type,
+            snap_len,
+            name: option_string(&options, IF_NAME),
+            description: option_string(&options, IF_DESCRIPTION),
+            ts_resolution,
+            ts_offset,
+            options,
+        }))
+    }
+}
+
+fn enhanced_packet_block(endianness: Endianness) -> impl Fn(&[u8]) -> ParseResult<EnhancedPacketBlock> {
+    move |input| {
+        let (input, interface_id) = context("interface id", u32(endianness))(input)?;
+        let (input, timestamp) = context("timestamp", timestamp(endianness))(input)?;
+        let (input, captured_packet_length) = context("captured length", u32(endianness))(input)?;
+        let (input, original_packet_length) = context("original length", u32(endianness))(input)?;
+        let (input, packet_data) = context("packet data", take(captured_packet_length))(input)?;
+        let (input, _padding) = take(padding_length(captured_packet_length as usize).min(input.len()))(input)?;
+        let (input, options) = block_options(endianness)(input)?;
+
+        let flags = find_option(&options, EPB_FLAGS)
+            .and_then(|option| u32::<_, nom::error::Error<&[u8]>>(endianness)(option.value.as_slice()).ok())
+            .map(|(_, flags)| flags);
+
+        Ok((input, EnhancedPacketBlock {
+            interface_id,
+            timestamp,
+            captured_packet_length,
+            original_packet_length,
+            packet_data: packet_data.to_vec(),
+            comment: option_string(&options, OPT_COMMENT),
+            flags,
+            options,
+        }))
+    }
+}
+
+fn simple_packet_block(endianness: Endianness, snap_len: u32) -> impl Fn(&[u8]) -> ParseResult<SimplePacketBlock> {
+    move |input| {
+        let (input, original_packet_length) = context("original length", u32(endianness))(input)?;
+        // The captured length is not stored, but follows from the original length and the snap length.
+        let captured_packet_length = if snap_len == 0 {
+            original_packet_length
+        } else {
+            original_packet_length.min(snap_len)
+        };
+        let (input, packet_data) = context("packet data", take(captured_packet_length))(input)?;
+
+        Ok((input, SimplePacketBlock {
+            original_packet_length,
+            packet_data: packet_data.to_vec(),
+        }))
+    }
+}
+
+fn name_resolution_block(endianness: Endianness) -> impl Fn(&[u8]) -> ParseResult<NameResolutionBlock> {
+    move |mut input| {
+        let mut records = Vec::new();
+        loop {
+            let (rest, record_type) = context("record type", u16(endianness))(input)?;
+            let (rest, length) = context("record length", u16(endianness))(rest)?;
+            if record_type == NRB_RECORD_END {
+                input = rest;
+                break;
+            }
+            let (rest, value) = context("record value", take(length))(rest)?;
+            let (rest, _padding) = take(padding_length(length as usize).min(rest.len()))(rest)?;
+            input = rest;
+
+            let record = match record_type {
+                NRB_RECORD_IPV4 if value.len() >= 4 => {
+                    let mut address = [0u8; 4];
+                    address.copy_from_slice(&value[..4]);
+                    NameResolutionRecord::Ipv4 { address: Ipv4Addr::from(address), names: names(&value[4..]) }
+                }
+                NRB_RECORD_IPV6 if value.len() >= 16 => {
+                    let mut address = [0u8; 16];
+                    address.copy_from_slice(&value[..16]);
+                    NameResolutionRecord::Ipv6 { address: Ipv6Addr::from(address), names: names(&value[16..]) }
+                }
+                _ => {
+                    NameResolutionRecord::Unknown { record_type, value: value.to_vec() }
+                }
+            };
+            records.push(record);
+        }
+        let (input, options) = block_options(endianness)(input)?;
+
+        Ok((input, NameResolutionBlock {
+            records,
+            options,
+        }))
+    }
+}
+
+fn interface_statistics_block(endianness: Endianness) -> impl Fn(&[u8]) -> ParseResult<InterfaceStatisticsBlock> {
+    move |input| {
+        let (input, interface_id) = context("interface id", u32(endianness))(input)?;
+        let (input, timestamp) = context("timestamp", timestamp(endianness))(input)?;
+        let (input, options) = block_options(endianness)(input)?;
+
+        let counter = |code: u16| find_option(&options, code)
+            .and_then(|option| u64::<_, nom::error::Error<&[u8]>>(endianness)(option.value.as_slice()).ok())
+            .map(|(_, counter)| counter);
+        let received = counter(ISB_IFRECV);
+        let dropped = counter(ISB_IFDROP);
+
+        Ok((input, InterfaceStatisticsBlock {
+            interface_id,
+            timestamp,
+            comment: option_string(&options, OPT_COMMENT),
+            received,
+            dropped,
+            options,
+        }))
+    }
+}
+
+/// Timestamps are stored as two 32-bit values, the high-order half first.
+fn timestamp(endianness: Endianness) -> impl Fn(&[u8]) -> ParseResult<u64> {
+    move |input| {
+        let (input, high) = u32(endianness)(input)?;
+        let (input, low) = u32(endianness)(input)?;
+        Ok((input, ((high as u64) << 32) | low as u64))
+    }
+}
+
+fn block_options(endianness: Endianness) -> impl Fn(&[u8]) -> ParseResult<Vec<BlockOption>> {
+    move |mut input| {
+        let mut options = Vec::new();
+        // The options list may be omitted entirely, or lack the closing opt_endofopt.
+        while !input.is_empty() {
+            let (rest, code) = context("option code", u16(endianness))(input)?;
+            let (rest, length) = context("option length", u16(endianness))(rest)?;
+            if code == OPT_ENDOFOPT {
+                input = rest;
+                break;
+            }
+            let (rest, value) = context("option value", take(length))(rest)?;
+            let (rest, _padding) = take(padding_length(length as usize).min(rest.len()))(rest)?;
+            options.push(BlockOption {
+                code,
+                value: value.to_vec(),
+            });
+            input = rest;
+        }
+        Ok((input, options))
+    }
+}
+
+fn find_option(options: &[BlockOption], code: u16) -> Option<&BlockOption> {
+    options.iter().find(|option| option.code == code)
+}
+
+fn option_string(options: &[BlockOption], code: u16) -> Option<String> {
+    find_option(options, code).map(BlockOption::as_string)
+}
+
+/// Splits the zero-terminated names of a name resolution record.
+fn names(value: &[u8]) -> Vec<String> {
+    value.split(|byte| *byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).to_string())
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_u16(buf: &mut Vec<u8>, endianness: Endianness, value: u16) {
+        match endianness {
+            Endianness::Big => buf.extend_from_slice(&value.to_be_bytes()),
+            _ => buf.extend_from_slice(&value.to_le_bytes()),
+        }
+    }
+
+    fn put_u32(buf: &mut Vec<u8>, endianness: Endianness, value: u32) {
+        match endianness {
+            Endianness::Big => buf.extend_from_slice(&value.to_be_bytes()),
+            _ => buf.extend_from_slice(&value.to_le_bytes()),
+        }
+    }
+
+    fn put_option(buf: &mut Vec<u8>, endianness: Endianness, code: u16, value: &[u8]) {
+        put_u16(buf, endianness, code);
+        put_u16(buf, endianness, value.len() as u16);
+        buf.extend_from_slice(value);
+        buf.resize(buf.len() + padding_length(value.len()), 0);
+    }
+
+    fn block(endianness: Endianness, block_type: u32, body: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        put_u32(&mut buf, endianness, block_type);
+        put_u32(&mut buf, endianness, body.len() as u32 + 12);
+        buf.extend_from_slice(body);
+        put_u32(&mut buf, endianness, body.len() as u32 + 12);
+        buf
+    }
+
+    fn section_bytes(endianness: Endianness) -> Vec<u8> {
+        let mut shb = Vec::new();
+        put_u32(&mut shb, endianness, BYTE_ORDER_MAGIC);
+        put_u16(&mut shb, endianness, 1);
+        put_u16(&mut shb, endianness, 0);
+        shb.extend_from_slice(&[0xFF; 8]);
+        put_option(&mut shb, endianness, SHB_OS, b"Linux");
+        put_option(&mut shb, endianness, OPT_ENDOFOPT, &[]);
+
+        let mut idb = Vec::new();
+        put_u16(&mut idb, endianness, 1);
+        put_u16(&mut idb, endianness, 0);
+        put_u32(&mut idb, endianness, 65535);
+        put_option(&mut idb, endianness, IF_NAME, b"eth0");
+        put_option(&mut idb, endianness, IF_TSRESOL, &[9]);
+        put_option(&mut idb, endianness, OPT_ENDOFOPT, &[]);
+
+        let mut epb = Vec::new();
+        put_u32(&mut epb, endianness, 0);
+        put_u32(&mut epb, endianness, 0);
+        put_u32(&mut epb, endianness, 1_500_000_000);
+        put_u32(&mut epb, endianness, 3);
+        put_u32(&mut epb, endianness, 3);
+        epb.extend_from_slice(&[1, 2, 3, 0]);
+        put_option(&mut epb, endianness, OPT_COMMENT, b"exercise start");
+
+        let mut spb = Vec::new();
+        put_u32(&mut spb, endianness, 2);
+        spb.extend_from_slice(&[4, 5, 0, 0]);
+
+        let mut nrb = Vec::new();
+        put_u16(&mut nrb, endianness, NRB_RECORD_IPV4);
+        put_u16(&mut nrb, endianness, 9);
+        nrb.extend_from_slice(&[10, 0, 0, 1, b'h', b'o', b's', b't', 0, 0, 0, 0]);
+        put_u16(&mut nrb, endianness, NRB_RECORD_END);
+        put_u16(&mut nrb, endianness, 0);
+
+        let mut isb = Vec::new();
+        put_u32(&mut isb, endianness, 0);
+        put_u32(&mut isb, endianness, 0);
+        put_u32(&mut isb, endianness, 0);
+
+        let mut buf = block(endianness, SECTION_HEADER_BLOCK, &shb);
+        buf.extend(block(endianness, INTERFACE_DESCRIPTION_BLOCK, &idb));
+        buf.extend(block(endianness, ENHANCED_PACKET_BLOCK, &epb));
+        buf.extend(block(endianness, SIMPLE_PACKET_BLOCK, &spb));
+        buf.extend(block(endianness, NAME_RESOLUTION_BLOCK, &nrb));
+        buf.extend(block(endianness, INTERFACE_STATISTICS_BLOCK, &isb));
+        buf
+    }
+
+    #[test]
+    fn parse_sections_in_both_byte_orders() {
+        let mut buf = section_bytes(Endianness::Little);
+        buf.extend(section_bytes(Endianness::Big));
+
+        let pcapng = PcapNG::try_from(buf.as_slice()).unwrap();
+        assert_eq!(pcapng.sections.len(), 2);
+        assert_eq!(pcapng.sections[1].header.endianness, Endianness::Big);
+        for section in &pcapng.sections {
+            assert_eq!(section.header.os.as_deref(), Some("Linux"));
+            assert_eq!(section.interfaces[0].name.as_deref(), Some("eth0"));
+            assert_eq!(section.interfaces[0].ts_resolution, TimestampResolution::NANOS);
+            assert_eq!(section.blocks.len(), 4);
+            if let Block::NameResolution(nrb) = &section.blocks[2] {
+                assert!(matches!(&nrb.records[0],
+                    NameResolutionRecord::Ipv4 { address, names } if *address == Ipv4Addr::new(10, 0, 0, 1) && names == &["host"]));
+            } else { panic!("Expected a Name Resolution Block"); }
+        }
+
+        let packets: Vec<PcapNgPacket> = pcapng.packets().collect();
+        assert_eq!(packets.len(), 4);
+        assert_eq!(packets[0].timestamp, Some(Duration::new(1, 500_000_000)));
+        assert_eq!(packets[0].packet_data, &[1, 2, 3]);
+        assert_eq!(packets[1].timestamp, None);
+        assert_eq!(packets[1].packet_data, &[4, 5]);
+    }
+
+    #[test]
+    fn reject_packet_for_unknown_interface() {
+        let mut buf = section_bytes(Endianness::Little);
+        let mut epb = Vec::new();
+        put_u32(&mut epb, Endianness::Little, 1);
+        epb.extend_from_slice(&[0; 16]);
+        buf.extend(block(Endianness::Little, ENHANCED_PACKET_BLOCK, &epb));
+
+        let err = PcapNG::try_from(buf.as_slice()).unwrap_err();
+        let expected = ParseError::new(buf.len() - epb.len() - 4, ParseErrorReason::UnknownInterface(1))
+            .with_packet_index(2)
+            .with_field("interface id");
+        assert_eq!(err.parse_error(), &expected);
+    }
+
+    #[test]
+    fn skip_packets_of_undescribed_interfaces() {
+        let section = Section {
+            header: SectionHeaderBlock::new(),
+            interfaces: Vec::new(),
+            blocks: vec![
+                Block::EnhancedPacket(EnhancedPacketBlock::new(0, 0, vec![1, 2, 3])),
+                Block::SimplePacket(SimplePacketBlock { original_packet_length: 2, packet_data: vec![4, 5] }),
+            ],
+        };
+        assert_eq!(section.packets().count(), 0);
+
+        let section = Section {
+            interfaces: vec![InterfaceDescriptionBlock::new(LINKTYPE_RAW as u16, 0)],
+            blocks: vec![
+                Block::EnhancedPacket(EnhancedPacketBlock::new(1, 0, vec![1, 2, 3])),
+                Block::SimplePacket(SimplePacketBlock { original_packet_length: 2, packet_data: vec![4, 5] }),
+            ],
+            ..section
+        };
+        let packets: Vec<PcapNgPacket> = section.packets().collect();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].packet_data, &[4, 5]);
+    }
+
+    #[test]
+    fn recover_from_damaged_blocks() {
+        let mut buf = section_bytes(Endianness::Little);
+        let mut damaged = block(Endianness::Little, ENHANCED_PACKET_BLOCK, &[0; 20]);
+        let last = damaged.len() - 1;
+        damaged[last] = 0xFF;
+        buf.extend(damaged);
+        buf.extend(section_bytes(Endianness::Little));
+        buf.truncate(buf.len() - 2);
+
+        assert!(PcapNG::try_from(buf.as_slice()).is_err());
+        let (pcapng, damage) = PcapNG::parse_lenient(&buf).unwrap();
+        assert_eq!(pcapng.sections.len(), 2);
+        assert_eq!(pcapng.packets().count(), 4);
+        assert_eq!(damage.len(), 2);
+        assert_eq!(damage[0].reason, ParseErrorReason::InvalidBlockLength(0xFF000020));
+        assert_eq!(damage[1].reason, ParseErrorReason::Truncated);
+    }
+}
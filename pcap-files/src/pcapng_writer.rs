@@ -0,0 +1,352 @@
+use std::io::{Error, ErrorKind, Write};
+use nom::number::Endianness;
+use crate::constants::*;
+use crate::pcap_writer::{write_u16, write_u32, write_u64};
+use crate::pcapng::{padding_length, Block, BlockOption, EnhancedPacketBlock, InterfaceDescriptionBlock,
+                    InterfaceStatisticsBlock, NameResolutionBlock, NameResolutionRecord, PcapNG,
+                    SectionHeaderBlock, SimplePacketBlock};
+use crate::timestamp::TimestampResolution;
+
+/// Writes .pcapng files to any `Write`, block by block.
+/// The Section Header Block is written on creation; interfaces must be written before the packets that refer to them.
+///
+/// The typed fields of the blocks (e.g. `comment`, `if_name`, `epb_flags`) are written as options,
+/// followed by any other options present in the generic `options` of a block.
+/// The section length is always written as unspecified (-1).
+pub struct PcapNgWriter<W: Write> {
+    writer: W,
+    endianness: Endianness,
+    interface_count: u32,
+}
+
+impl<W: Write> PcapNgWriter<W> {
+    pub fn new(writer: W, header: &SectionHeaderBlock) -> Result<Self, Error> {
+        let mut pcapng_writer = Self {
+            writer,
+            endianness: header.endianness,
+            interface_count: 0,
+        };
+        pcapng_writer.write_section_header(header)?;
+        Ok(pcapng_writer)
+    }
+
+    /// Starts a new section, possibly with a different byte order. Interface ids start again at 0.
+    pub fn write_section_header(&mut self, header: &SectionHeaderBlock) -> Result<(), Error> {
+        self.endianness = header.endianness;
+        self.interface_count = 0;
+
+        let e = self.endianness;
+        let mut body = Vec::new();
+        write_u32(&mut body, e, BYTE_ORDER_MAGIC)?;
+        write_u16(&mut body, e, header.major_version)?;
+        write_u16(&mut body, e, header.minor_version)?;
+        write_u64(&mut body, e, u64::MAX)?; // section length not specified
+        let mut options = OptionsWriter::new(e);
+        options.string(OPT_COMMENT, &header.comment)?;
+        options.string(SHB_HARDWARE, &header.hardware)?;
+        options.string(SHB_OS, &header.os)?;
+        options.string(SHB_USERAPPL, &header.user_application)?;
+        options.others(&header.options, &[OPT_COMMENT, SHB_HARDWARE, SHB_OS, SHB_USERAPPL])?;
+        body.extend(options.finish()?);
+
+        self.write_block(SECTION_HEADER_BLOCK, &body)
+    }
+
+    /// Writes an Interface Description Block, returning the interface id to use for packets captured on it.
+    pub fn write_interface(&mut self, interface: &InterfaceDescriptionBlock) -> Result<u32, Error> {
+        let e = self.endianness;
+        let mut body = Vec::new();
+        write_u16(&mut body, e, interface.link_type)?;
+        write_u16(&mut body, e, 0)?; // reserved
+        write_u32(&mut body, e, interface.snap_len)?;
+        let mut options = OptionsWriter::new(e);
+        options.string(IF_NAME, &interface.name)?;
+        options.string(IF_DESCRIPTION, &interface.description)?;
+        if interface.ts_resolution != TimestampResolution::default() {
+            options.option(IF_TSRESOL, &[interface.ts_resolution.to_if_tsresol()])?;
+        }
+        if interface.ts_offset != 0 {
+            let mut value = Vec::new();
+            write_u64(&mut value, e, interface.ts_offset as u64)?;
+            options.option(IF_TSOFFSET, &value)?;
+        }
+        options.others(&interface.options, &[IF_NAME, IF_DESCRIPTION, IF_TSRESOL, IF_TSOFFSET])?;
+        body.extend(options.finish()?);
+
+        self.write_block(INTERFACE_DESCRIPTION_BLOCK, &body)?;
+        self.interface_count += 1;
+        Ok(self.interface_count - 1)
+    }
+
+    pub fn write_enhanced_packet(&mut self, packet: &EnhancedPacketBlock) -> Result<(), Error> {
+        self.verify_interface(packet.interface_id)?;
+        if packet.captured_packet_length as usize != packet.packet_data.len() {
+            return Err(Error::new(ErrorKind::InvalidInput,
+                format!("Captured packet length {} does not match the length of the packet data {}",
+                        packet.captured_packet_length, packet.packet_data.len())));
+        }
+
+        let e = self.endianness;
+        let mut body = Vec::new();
+        write_u32(&mut body, e, packet.interface_id)?;
+        write_timestamp(&mut body, e, packet.timestamp)?;
+        write_u32(&mut body, e, packet.captured_packet_length)?;
+        write_u32(&mut body, e, packet.original_packet_length)?;
+        write_padded(&mut body, &packet.packet_data)?;
+        let mut options = OptionsWriter::new(e);
+        options.string(OPT_COMMENT, &packet.comment)?;
+        if let Some(flags) = packet.flags {
+            let mut value = Vec::new();
+            write_u32(&mut value, e, flags)?;
+            options.option(EPB_FLAGS, &value)?;
+        }
+        options.others(&packet.options, &[OPT_COMMENT, EPB_FLAGS])?;
+        body.extend(options.finish()?);
+
+        self.write_block(ENHANCED_PACKET_BLOCK, &body)
+    }
+
+    pub fn write_simple_packet(&mut self, packet: &SimplePacketBlock) -> Result<(), Error> {
+        self.verify_interface(0)?;
+
+        let mut body = Vec::new();
+        write_u32(&mut body, self.endianness, packet.original_packet_length)?;
+        write_padded(&mut body, &packet.packet_data)?;
+
+        self.write_block(SIMPLE_PACKET_BLOCK, &body)
+    }
+
+    pub fn write_name_resolution(&mut self, block: &NameResolutionBlock) -> Result<(), Error> {
+        let e = self.endianness;
+        let mut body = Vec::new();
+        for record in &block.records {
+            let (record_type, value) = match record {
+                NameResolutionRecord::Ipv4 { address, names } => {
+                    (NRB_RECORD_IPV4, [address.octets().as_slice(), &names_value(names)].concat())
+                }
+                NameResolutionRecord::Ipv6 { address, names } => {
+                    (NRB_RECORD_IPV6, [address.octets().as_slice(), &names_value(names)].concat())
+                }
+                NameResolutionRecord::Unknown { record_type, value } => { (*record_type, value.clone()) }
+            };
+            write_u16(&mut body, e, record_type)?;
+            write_u16(&mut body, e, option_length(&value)?)?;
+            write_padded(&mut body, &value)?;
+        }
+        write_u16(&mut body, e, NRB_RECORD_END)?;
+        write_u16(&mut body, e, 0)?;
+        let mut options = OptionsWriter::new(e);
+        options.others(&block.options, &[])?;
+        body.extend(options.finish()?);
+
+        self.write_block(NAME_RESOLUTION_BLOCK, &body)
+    }
+
+    pub fn write_interface_statistics(&mut self, block: &InterfaceStatisticsBlock) -> Result<(), Error> {
+        self.verify_interface(block.interface_id)?;
+
+        let e = self.endianness;
+        let mut body = Vec::new();
+        write_u32(&mut body, e, block.interface_id)?;
+        write_timestamp(&mut body, e, block.timestamp)?;
+        let mut options = OptionsWriter::new(e);
+        options.string(OPT_COMMENT, &block.comment)?;
+        for (code, counter) in [(ISB_IFRECV, block.received), (ISB_IFDROP, block.dropped)] {
+            if let Some(counter) = counter {
+                let mut value = Vec::new();
+                write_u64(&mut value, e, counter)?;
+                options.option(code, &value)?;
+            }
+        }
+        options.others(&block.options, &[OPT_COMMENT, ISB_IFRECV, ISB_IFDROP])?;
+        body.extend(options.finish()?);
+
+        self.write_block(INTERFACE_STATISTICS_BLOCK, &body)
+    }
+
+    /// Writes any block that can follow the interfaces in a section.
+    pub fn write(&mut self, block: &Block) -> Result<(), Error> {
+        match block {
+            Block::EnhancedPacket(epb) => { self.write_enhanced_packet(epb) }
+            Block::SimplePacket(spb) => { self.write_simple_packet(spb) }
+            Block::NameResolution(nrb) => { self.write_name_resolution(nrb) }
+            Block::InterfaceStatistics(isb) => { self.write_interface_statistics(isb) }
+            Block::Unknown { block_type, body } => { self.write_block(*block_type, body) }
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn verify_interface(&self, interface_id: u32) -> Result<(), Error> {
+        if interface_id < self.interface_count {
+            Ok(())
+        } else {
+            Err(Error::new(ErrorKind::InvalidInput,
+                format!("Interface {interface_id} has not been written to the current section")))
+        }
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> Result<(), Error> {
+        let padded_length = body.len() + padding_length(body.len());
+        let block_total_length = u32::try_from(padded_length + 12)
+            .map_err(|_| Error::new(ErrorKind::InvalidInput, "Block exceeds the maximum block size"))?;
+        write_u32(&mut self.writer, self.endianness, block_type)?;
+        write_u32(&mut self.writer, self.endianness, block_total_length)?;
+        write_padded(&mut self.writer, body)?;
+        write_u32(&mut self.writer, self.endianness, block_total_length)
+    }
+}
+
+impl PcapNG {
+    /// Serializes all sections, with their interfaces and blocks, to `writer`.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<W, Error> {
+        let Some(first) = self.sections.first() else {
+            return Err(Error::new(ErrorKind::InvalidInput, "A .pcapng file needs at least one section"));
+        };
+        let mut pcapng_writer = PcapNgWriter::new(writer, &first.header)?;
+        for (index, section) in self.sections.iter().enumerate() {
+            if index > 0 {
+                pcapng_writer.write_section_header(&section.header)?;
+            }
+            for interface in &section.interfaces {
+                pcapng_writer.write_interface(interface)?;
+            }
+            for block in &section.blocks {
+                pcapng_writer.write(block)?;
+            }
+        }
+        pcapng_writer.flush()?;
+        Ok(pcapng_writer.into_inner())
+    }
+}
+
+/// Collects the options of a block; the list is closed with opt_endofopt when there are any options.
+struct OptionsWriter {
+    endianness: Endianness,
+    buf: Vec<u8>,
+}
+
+impl OptionsWriter {
+    fn new(endianness: Endianness) -> Self {
+        Self {
+            endianness,
+            buf: Vec::new(),
+        }
+    }
+
+    fn option(&mut self, code: u16, value: &[u8]) -> Result<(), Error> {
+        write_u16(&mut self.buf, self.endianness, code)?;
+        write_u16(&mut self.buf, self.endianness, option_length(value)?)?;
+        write_padded(&mut self.buf, value)
+    }
+
+    fn string(&mut self, code: u16, value: &Option<String>) -> Result<(), Error> {
+        match value {
+            Some(value) => { self.option(code, value.as_bytes()) }
+            None => { Ok(()) }
+        }
+    }
+
+    /// Writes the generic options, except for those with codes that were already written from typed fields.
+    fn others(&mut self, options: &[BlockOption], skip_codes: &[u16]) -> Result<(), Error> {
+        options.iter()
+            .filter(|option| option.code != OPT_ENDOFOPT && !skip_codes.contains(&option.code))
+            .try_for_each(|option| self.option(option.code, &option.value))
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, Error> {
+        if !self.buf.is_empty() {
+            write_u16(&mut self.buf, self.endianness, OPT_ENDOFOPT)?;
+            write_u16(&mut self.buf, self.endianness, 0)?;
+        }
+        Ok(self.buf)
+    }
+}
+
+fn write_timestamp<W: Write>(writer: &mut W, endianness: Endianness, timestamp: u64) -> Result<(), Error> {
+    write_u32(writer, endianness, (timestamp >> 32) as u32)?;
+    write_u32(writer, endianness, timestamp as u32)
+}
+
+fn write_padded<W: Write>(writer: &mut W, value: &[u8]) -> Result<(), Error> {
+    writer.write_all(value)?;
+    writer.write_all(&[0u8; 3][..padding_length(value.len())])
+}
+
+fn option_length(value: &[u8]) -> Result<u16, Error> {
+    u16::try_from(value.len())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "Option value exceeds 65535 bytes"))
+}
+
+fn names_value(names: &[String]) -> Vec<u8> {
+    names.iter()
+        .flat_map(|name| name.bytes().chain(std::iter::once(0)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::pcapng::PcapNgPacket;
+    use super::*;
+
+    #[test]
+    fn round_trip_options_and_packets() {
+        for endianness in [Endianness::Little, Endianness::Big] {
+            let header = SectionHeaderBlock {
+                endianness,
+                hardware: Some("x86_64".to_string()),
+                os: Some("Linux".to_string()),
+                user_application: Some("packet-rehash".to_string()),
+                ..SectionHeaderBlock::new()
+            };
+            let interface = InterfaceDescriptionBlock {
+                name: Some("eth0".to_string()),
+                ts_resolution: TimestampResolution::NANOS,
+                ..InterfaceDescriptionBlock::new(1, 65535)
+            };
+            let timestamp = interface.ticks(Duration::new(1_700_000_000, 123_456_789));
+            let packet = EnhancedPacketBlock {
+                comment: Some("exercise start".to_string()),
+                flags: Some(0x1),
+                ..EnhancedPacketBlock::new(0, timestamp, vec![1, 2, 3, 4, 5])
+            };
+
+            let mut writer = PcapNgWriter::new(Vec::new(), &header).unwrap();
+            assert_eq!(writer.write_interface(&interface).unwrap(), 0);
+            writer.write_enhanced_packet(&packet).unwrap();
+            writer.write_simple_packet(&SimplePacketBlock { original_packet_length: 2, packet_data: vec![6, 7] }).unwrap();
+            let bytes = writer.into_inner();
+
+            let pcapng = PcapNG::try_from(bytes.as_slice()).unwrap();
+            let section = &pcapng.sections[0];
+            assert_eq!(section.header.endianness, endianness);
+            assert_eq!(section.header.user_application.as_deref(), Some("packet-rehash"));
+            assert_eq!(section.interfaces[0].name.as_deref(), Some("eth0"));
+            assert_eq!(section.interfaces[0].ts_resolution, TimestampResolution::NANOS);
+            if let Block::EnhancedPacket(epb) = &section.blocks[0] {
+                assert_eq!(epb.comment.as_deref(), Some("exercise start"));
+                assert_eq!(epb.flags, Some(0x1));
+            } else { panic!("Expected an Enhanced Packet Block"); }
+
+            let packets: Vec<PcapNgPacket> = pcapng.packets().collect();
+            assert_eq!(packets[0].timestamp, Some(Duration::new(1_700_000_000, 123_456_789)));
+            assert_eq!(packets[0].packet_data, &[1, 2, 3, 4, 5]);
+            assert_eq!(packets[1].packet_data, &[6, 7]);
+
+            assert_eq!(pcapng.write_to(Vec::new()).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn reject_packet_before_interface() {
+        let mut writer = PcapNgWriter::new(Vec::new(), &SectionHeaderBlock::new()).unwrap();
+        assert!(writer.write_enhanced_packet(&EnhancedPacketBlock::new(0, 0, vec![1])).is_err());
+    }
+}
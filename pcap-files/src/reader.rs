@@ -0,0 +1,339 @@
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use memmap2::Mmap;
+use crate::constants::{PCAP_FILE_HEADER_LENGTH, PCAP_RECORD_HEADER_LENGTH};
+use crate::error::parse_error;
+use crate::pcap::{parse_pcap_header, pcap_packet_record, pcap_record_header, resync_pcap_record, PcapFileHeader, PcapPacketRecord, PcapPacketRef};
+use crate::{ParseError, ParseErrorReason, PcapError};
+
+/// Streams the packet records of a .pcap file from any `Read`, one record at a time,
+/// so that only a single packet is held in memory.
+pub struct PcapReader<R: Read> {
+    reader: R,
+    header: PcapFileHeader,
+    offset: usize,          // byte offset of the next record
+    packet_index: usize,    // index of the next record
+    done: bool,
+}
+
+impl<R: Read> PcapReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, PcapError> {
+        let mut buf = [0u8; PCAP_FILE_HEADER_LENGTH];
+        let filled = read_up_to(&mut reader, &mut buf)
+            .map_err(|err| PcapError::ParsePcapError(ParseError::new(0, err)))?;
+        let (_, header) = parse_pcap_header(&buf[..filled])?;
+
+        Ok(Self {
+            reader,
+            header,
+            offset: PCAP_FILE_HEADER_LENGTH,
+            packet_index: 0,
+            done: false,
+        })
+    }
+
+    pub fn header(&self) -> &PcapFileHeader {
+        &self.header
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_record(&mut self) -> Result<Option<PcapPacketRecord>, ParseError> {
+        let mut buf = [0u8; PCAP_RECORD_HEADER_LENGTH];
+        let filled = read_up_to(&mut self.reader, &mut buf)
+            .map_err(|err| ParseError::new(self.offset, err))?;
+        if filled == 0 {
+            return Ok(None);
+        }
+        let (_, record_header) = pcap_record_header(self.header.endianness, self.header.snap_len)(&buf[..filled])
+            .map_err(|err| {
+                let mut err = parse_error(&buf, err);
+                err.offset += self.offset;
+                err
+            })?;
+
+        let data_offset = self.offset + PCAP_RECORD_HEADER_LENGTH;
+        // The length comes from the file, so read up to it rather than allocating it up front,
+        // as a damaged record without a snapshot length to check against can claim up to 4 GiB.
+        let mut packet_data = Vec::new();
+        (&mut self.reader).take(record_header.captured_packet_length as u64).read_to_end(&mut packet_data)
+            .map_err(|err| ParseError::new(data_offset + packet_data.len(), ParseErrorReason::Io(err.to_string())))?;
+        if packet_data.len() < record_header.captured_packet_length as usize {
+            return Err(ParseError::new(data_offset, ParseErrorReason::Truncated).with_field("packet data"));
+        }
+        self.offset = data_offset + packet_data.len();
+
+        Ok(Some(PcapPacketRecord {
+            ts_secs: record_header.ts_secs,
+            ts_secs_fraction: record_header.ts_secs_fraction,
+            captured_packet_length: record_header.captured_packet_length,
+            original_packet_length: record_header.original_packet_length,
+            packet_data,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = Result<PcapPacketRecord, PcapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = self.read_record()
+            .map_err(|err| PcapError::ParsePcapError(err.with_packet_index(self.packet_index)));
+        match record {
+            Ok(Some(_)) => { self.packet_index += 1; }
+            _ => { self.done = true; }
+        }
+        record.transpose()
+    }
+}
+
+impl TryFrom<File> for PcapReader<BufReader<File>> {
+    type Error = PcapError;
+
+    fn try_from(file: File) -> Result<Self, Self::Error> {
+        PcapReader::new(BufReader::new(file))
+    }
+}
+
+/// Reads until `buf` is full or the reader is at its end, returning the number of bytes read.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, ParseErrorReason> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => { break; }
+            Ok(n) => { filled += n; }
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => { return Err(ParseErrorReason::Io(err.to_string())); }
+        }
+    }
+    Ok(filled)
+}
+
+/// A zero-copy view of a complete .pcap file in memory; packets are parsed lazily while iterating.
+#[derive(Clone, Debug)]
+pub struct PcapSlice<'a> {
+    header: PcapFileHeader,
+    buf: &'a [u8],
+}
+
+impl<'a> PcapSlice<'a> {
+    pub fn header(&self) -> &PcapFileHeader {
+        &self.header
+    }
+
+    pub fn packets(&self) -> PcapPacketRefs<'a> {
+        PcapPacketRefs {
+            header: self.header.clone(),
+            buf: self.buf,
+            input: &self.buf[PCAP_FILE_HEADER_LENGTH..],
+            packet_index: 0,
+            lenient: false,
+            done: false,
+        }
+    }
+
+    /// Iterates the packets of a possibly damaged file. A damaged record is reported as an error,
+    /// after which iteration resumes at the next record that looks valid, if any.
+    pub fn packets_lenient(&self) -> PcapPacketRefs<'a> {
+        PcapPacketRefs {
+            lenient: true,
+            ..self.packets()
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for PcapSlice<'a> {
+    type Error = PcapError;
+
+    fn try_from(buf: &'a [u8]) -> Result<Self, Self::Error> {
+        let (_, header) = parse_pcap_header(buf)?;
+        Ok(Self {
+            header,
+            buf,
+        })
+    }
+}
+
+/// Iterator over the packets of a `PcapSlice`, borrowing the packet data from the slice.
+pub struct PcapPacketRefs<'a> {
+    header: PcapFileHeader,
+    buf: &'a [u8],          // the complete file, to locate errors
+    input: &'a [u8],
+    packet_index: usize,
+    lenient: bool,          // resynchronise after a damaged record instead of stopping
+    done: bool,
+}
+
+impl<'a> Iterator for PcapPacketRefs<'a> {
+    type Item = Result<PcapPacketRef<'a>, PcapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.input.is_empty() {
+            return None;
+        }
+        match pcap_packet_record(self.header.endianness, self.header.snap_len)(self.input) {
+            Ok((rest, packet)) => {
+                self.input = rest;
+                self.packet_index += 1;
+                Some(Ok(packet))
+            }
+            Err(err) => {
+                let err = parse_error(self.buf, err).with_packet_index(self.packet_index);
+                let resynced = if self.lenient {
+                    resync_pcap_record(&self.header, self.input)
+                } else {
+                    None
+                };
+                match resynced {
+                    Some(rest) => { self.input = rest; }
+                    None => { self.done = true; }
+                }
+                Some(Err(PcapError::ParsePcapError(err)))
+            }
+        }
+    }
+}
+
+/// A .pcap file mapped into memory, so that the operating system pages packet data in and out as needed
+/// instead of the whole file being read into memory.
+#[derive(Debug)]
+pub struct MappedPcap {
+    mmap: Mmap,
+    header: PcapFileHeader,
+}
+
+impl MappedPcap {
+    pub fn header(&self) -> &PcapFileHeader {
+        &self.header
+    }
+
+    pub fn as_slice(&self) -> PcapSlice<'_> {
+        PcapSlice {
+            header: self.header.clone(),
+            buf: &self.mmap,
+        }
+    }
+
+    pub fn packets(&self) -> PcapPacketRefs<'_> {
+        self.as_slice().packets()
+    }
+
+    pub fn packets_lenient(&self) -> PcapPacketRefs<'_> {
+        self.as_slice().packets_lenient()
+    }
+}
+
+impl TryFrom<File> for MappedPcap {
+    type Error = PcapError;
+
+    fn try_from(file: File) -> Result<Self, Self::Error> {
+        // Safety: the mapping is read-only; modifying or truncating the file while it is mapped
+        // is undefined behaviour, which is accepted for recordings that are not being written to.
+        let mmap = unsafe { Mmap::map(&file) }
+            .map_err(|err| PcapError::ParsePcapError(ParseError::new(0, ParseErrorReason::Io(err.to_string()))))?;
+        let (_, header) = parse_pcap_header(&mmap)?;
+        Ok(Self {
+            mmap,
+            header,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pcap::Pcap;
+    use crate::pcap_writer::PcapWriter;
+    use super::*;
+
+    fn capture() -> Vec<u8> {
+        let mut writer = PcapWriter::new(Vec::new(), &PcapFileHeader::new(1, 65535)).unwrap();
+        for i in 0..3u8 {
+            writer.write_packet(&PcapPacketRecord {
+                ts_secs: i as u32,
+                ts_secs_fraction: 0,
+                captured_packet_length: 2,
+                original_packet_length: 2,
+                packet_data: vec![i, i],
+            }).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    #[test]
+    fn stream_and_borrow_match_full_parse() {
+        let bytes = capture();
+        let pcap = Pcap::try_from(bytes.as_slice()).unwrap();
+
+        let streamed: Vec<PcapPacketRecord> = PcapReader::new(bytes.as_slice()).unwrap()
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(streamed, pcap.packets);
+
+        let borrowed: Vec<PcapPacketRecord> = PcapSlice::try_from(bytes.as_slice()).unwrap().packets()
+            .map(|packet| packet.map(|packet| packet.to_record()))
+            .collect::<Result<_, _>>().unwrap();
+        assert_eq!(borrowed, pcap.packets);
+    }
+
+    #[test]
+    fn report_implausible_record_length_without_allocating_it() {
+        let mut writer = PcapWriter::new(Vec::new(), &PcapFileHeader::new(1, 0)).unwrap();
+        writer.write_packet(&PcapPacketRecord {
+            ts_secs: 0,
+            ts_secs_fraction: 0,
+            captured_packet_length: 4,
+            original_packet_length: 4,
+            packet_data: vec![1, 2, 3, 4],
+        }).unwrap();
+        let mut bytes = writer.into_inner();
+        let length = PCAP_FILE_HEADER_LENGTH + 8;
+        bytes[length..length + 8].copy_from_slice(&[0xF0, 0xFF, 0xFF, 0xFF, 0xF0, 0xFF, 0xFF, 0xFF]);
+
+        let streamed: Vec<Result<PcapPacketRecord, PcapError>> = PcapReader::new(bytes.as_slice()).unwrap().collect();
+        assert_eq!(streamed.len(), 1);
+        assert!(matches!(&streamed[0], Err(PcapError::ParsePcapError(err)) if err.reason == ParseErrorReason::Truncated));
+    }
+
+    #[test]
+    fn report_truncated_record() {
+        let bytes = capture();
+        let truncated = &bytes[..bytes.len() - 1];
+
+        let streamed: Vec<Result<PcapPacketRecord, PcapError>> = PcapReader::new(truncated).unwrap().collect();
+        assert_eq!(streamed.len(), 3);
+        assert!(streamed[2].is_err());
+
+        let borrowed: Vec<Result<PcapPacketRef, PcapError>> = PcapSlice::try_from(truncated).unwrap().packets().collect();
+        assert_eq!(borrowed.len(), 3);
+        assert!(borrowed[2].is_err());
+
+        // Both report the same location: the packet data of the third record.
+        let expected = ParseError::new(24 + 2 * 18 + 16, ParseErrorReason::Truncated)
+            .with_packet_index(2)
+            .with_field("packet data");
+        assert_eq!(streamed[2].as_ref().unwrap_err().parse_error(), &expected);
+        assert_eq!(borrowed[2].as_ref().unwrap_err().parse_error(), &expected);
+    }
+
+    #[test]
+    fn recover_from_corrupt_and_truncated_records() {
+        let bytes = capture();
+        let (pcap, damage) = Pcap::parse_lenient(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(pcap.packets.len(), 2);
+        assert_eq!(damage.len(), 1);
+        assert_eq!(damage[0].reason, ParseErrorReason::Truncated);
+
+        // Corrupt the captured length of the second record; the third record is found again.
+        let mut bytes = capture();
+        bytes[24 + 18 + 8 + 3] = 0xFF;
+        let (pcap, damage) = Pcap::parse_lenient(&bytes).unwrap();
+        assert_eq!(pcap.packets.iter().map(|packet| packet.ts_secs).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(damage.len(), 1);
+        assert_eq!(damage[0].offset, 24 + 18 + 8);
+        assert!(Pcap::try_from(bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Resolution of the timestamps of captured packets.
+/// Follows the encoding of the pcapng `if_tsresol` option: either a negative power of 10 or of 2.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimestampResolution {
+    Decimal(u8),    // timestamps are expressed in units of 10^-n seconds
+    Binary(u8),     // timestamps are expressed in units of 2^-n seconds
+}
+
+impl TimestampResolution {
+    pub const MICROS: TimestampResolution = TimestampResolution::Decimal(6);
+    pub const NANOS: TimestampResolution = TimestampResolution::Decimal(9);
+
+    /// Decodes the value of an `if_tsresol` option; the most significant bit selects base 2 over base 10.
+    pub fn from_if_tsresol(value: u8) -> Self {
+        if value & 0x80 == 0 {
+            TimestampResolution::Decimal(value)
+        } else {
+            TimestampResolution::Binary(value & 0x7F)
+        }
+    }
+
+    pub fn to_if_tsresol(&self) -> u8 {
+        match self {
+            TimestampResolution::Decimal(exponent) => { *exponent & 0x7F }
+            TimestampResolution::Binary(exponent) => { 0x80 | (*exponent & 0x7F) }
+        }
+    }
+
+    /// The number of timestamp units (ticks) in one second.
+    pub fn ticks_per_second(&self) -> u128 {
+        match self {
+            TimestampResolution::Decimal(exponent) => { 10u128.saturating_pow(*exponent as u32) }
+            TimestampResolution::Binary(exponent) => { 1u128 << (*exponent).min(127) }
+        }
+    }
+
+    /// Converts a timestamp expressed in units of this resolution to a `Duration`.
+    pub fn to_duration(&self, ticks: u64) -> Duration {
+        let ticks_per_second = self.ticks_per_second();
+        let ticks = ticks as u128;
+        let seconds = ticks / ticks_per_second;
+        let nanos = (ticks % ticks_per_second) * NANOS_PER_SEC / ticks_per_second;
+        Duration::new(seconds as u64, nanos as u32)
+    }
+
+    /// Converts a `Duration` to a timestamp expressed in units of this resolution, truncating any remainder.
+    /// Timestamps beyond the range of the resolution saturate to `u64::MAX`.
+    pub fn to_ticks(&self, duration: Duration) -> u64 {
+        let ticks_per_second = self.ticks_per_second();
+        let ticks = (duration.as_secs() as u128).saturating_mul(ticks_per_second)
+            .saturating_add((duration.subsec_nanos() as u128).saturating_mul(ticks_per_second) / NANOS_PER_SEC);
+        ticks.min(u64::MAX as u128) as u64
+    }
+}
+
+impl Default for TimestampResolution {
+    fn default() -> Self {
+        TimestampResolution::MICROS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_ticks() {
+        let duration = Duration::new(1_700_000_000, 123_456_789);
+        assert_eq!(TimestampResolution::NANOS.to_ticks(duration), 1_700_000_000_123_456_789);
+        assert_eq!(TimestampResolution::MICROS.to_duration(1_500_000), Duration::from_millis(1_500));
+        // Resolutions from a damaged if_tsresol saturate rather than overflow.
+        assert_eq!(TimestampResolution::Decimal(120).to_ticks(duration), u64::MAX);
+        assert_eq!(TimestampResolution::Binary(127).to_ticks(Duration::from_nanos(1)), u64::MAX);
+        assert_eq!(TimestampResolution::Binary(127).to_ticks(Duration::ZERO), 0);
+    }
+}
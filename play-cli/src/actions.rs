@@ -13,11 +13,16 @@ pub enum Action {
     Quit,
     PlayPause,
     Rewind,
+    SpeedUp,
+    SpeedDown,
+    Unthrottled,
+    StepForward,
+    StepBackward,
 }
 
 impl Action {
     pub fn iterator() -> Iter<'static, Action> {
-        static ACTIONS : [Action; 9] = [
+        static ACTIONS : [Action; 14] = [
             Action::MoveLeft,
             Action::MoveRight,
             Action::MoveUp,
@@ -27,6 +32,11 @@ impl Action {
             Action::Quit,
             Action::PlayPause,
             Action::Rewind,
+            Action::SpeedUp,
+            Action::SpeedDown,
+            Action::Unthrottled,
+            Action::StepForward,
+            Action::StepBackward,
         ];
         ACTIONS.iter()
     }
@@ -42,13 +52,18 @@ impl Action {
             Action::Quit => &[Key::Ctrl('c'), Key::Char('q')],
             Action::PlayPause => &[Key::Char('p'), Key::Char(' ')],
             Action::Rewind => &[Key::Char('r')],
+            Action::SpeedUp => &[Key::Char('+'), Key::Char('=')],
+            Action::SpeedDown => &[Key::Char('-')],
+            Action::Unthrottled => &[Key::Char('u')],
+            Action::StepForward => &[Key::Char('.')],
+            Action::StepBackward => &[Key::Char(',')],
         }
     }
 
     pub fn from_key(key: &Key) -> Option<Self> {
         Action::iterator()
-            .find(|action| action.key_mapping().contains(&key))
-            .and_then(|action|Some(action.clone()))
+            .find(|action| action.key_mapping().contains(key))
+            .copied()
     }
 }
 
@@ -64,6 +79,11 @@ impl Display for Action {
             Action::Quit => "Quit",
             Action::PlayPause => "Play/Pause",
             Action::Rewind => "Rewind",
+            Action::SpeedUp => "Faster",
+            Action::SpeedDown => "Slower",
+            Action::Unthrottled => "Unthrottled",
+            Action::StepForward => "Step Forward",
+            Action::StepBackward => "Step Backward",
         };
         write!(f, "{text}")
     }
@@ -5,10 +5,11 @@ mod actions;
 use std::env;
 use std::process::exit;
 use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
 
 use clap::Parser;
 use log::error;
-use packet_play::{Player, PlayerOptions, Recording};
+use packet_play::{file_sink, raw_sink, Command, Event, Player, PlayerOptions, PlayerState, Recording, Repetitions};
 
 const ERROR_CANNOT_START : i32 = 1;
 const ERROR_RUNTIME : i32 = 2;
@@ -29,27 +30,92 @@ fn main() {
         let (event_sender, event_receiver) = mpsc::channel();
 
         // Spawn thread for the Player
-        let player_handle = Player::builder()
+        let mut player_builder = Player::builder()
             .recording(recording)
             .destination(options.destination)
+            .destination_mode(options.destination_mode)
+            .mappings(options.mappings.clone())
+            .filter(options.filter.clone().unwrap_or_default())
+            .bpf(options.bpf.clone())
+            .bind_address(options.bind_address.clone())
             .source_port(options.source_port)
             .ttl(options.ttl)
+            .multicast(options.multicast())
+            .speed(options.playback_speed())
+            .repeat(options.repeat())
+            .send_failure(options.send_failure)
             .cmd_rx(cmd_receiver)
-            .event_tx(event_sender)
-            .build().expect("Failed to initialise Player.");
+            .event_tx(event_sender);
 
-        let input_handler = input::InputHandler::new(250);
+        if let Some(output) = &options.output {
+            if options.repeat().repetitions == Repetitions::Forever {
+                eprintln!("Cannot loop forever when writing to a file, give the number of loops.");
+                exit(ERROR_CANNOT_START);
+            }
+            match file_sink(output) {
+                Ok(sink) => { player_builder = player_builder.sink(sink).simulate(options.output_start); }
+                Err(error) => {
+                    eprintln!("Cannot write to {output}, because: {error}");
+                    exit(ERROR_CANNOT_START);
+                }
+            }
+        } else if let Some(interface) = &options.raw {
+            match raw_sink(interface, &options.rewrites, options.recalculate_checksums) {
+                Ok(sink) => { player_builder = player_builder.sink(sink); }
+                Err(error) => {
+                    eprintln!("Cannot replay on interface {interface}, because: {error}");
+                    exit(ERROR_CANNOT_START);
+                }
+            }
+        }
+        let player_handle = player_builder.build().expect("Failed to initialise Player.");
 
-        // Start the tui
-        if let Err(error) = tui::run_tui(options, event_receiver, cmd_sender, input_handler) {
-            error!("{:?}", error);
-            exit(ERROR_RUNTIME);
+        if let Some(output) = &options.output {
+            // Writing to a file needs no interaction, so play to the end without the tui.
+            let errors = write_output(event_receiver, cmd_sender);
+            if errors > 0 {
+                eprintln!("Replayed {} to {output} with {errors} errors", options.file);
+                let _ = player_handle.join();
+                exit(ERROR_RUNTIME);
+            }
+            println!("Replayed {} to {output}", options.file);
+        } else {
+            let input_handler = input::InputHandler::new(250);
+
+            // Start the tui
+            if let Err(error) = tui::run_tui(options, event_receiver, cmd_sender, input_handler) {
+                error!("{:?}", error);
+                exit(ERROR_RUNTIME);
+            }
         }
 
-        player_handle.join().expect("Could not join on the Player thread.");
+        if let Err(error) = player_handle.join().expect("Could not join on the Player thread.") {
+            eprintln!("Playback stopped, because: {error}");
+            exit(ERROR_RUNTIME);
+        }
     } else {
         let error = recording.unwrap_err();
-        error!("Cannot play recording, because: {:?}", error);
+        // The tui logger only shows messages once the tui runs, so report directly to the terminal.
+        eprintln!("Cannot play recording, because: {error}");
         exit(ERROR_CANNOT_START);
     };
 }
+
+/// Plays the recording into the output file until finished, reporting errors to the terminal.
+/// Returns the number of errors. Nobody can resume the playback, so a pause after a failure ends it.
+fn write_output(event_receiver: Receiver<Event>, cmd_sender: Sender<Command>) -> usize {
+    let _ = cmd_sender.send(Command::Play);
+    let mut errors = 0;
+    for event in event_receiver.iter() {
+        match event {
+            Event::Error(error) => {
+                eprintln!("{error}");
+                errors += 1;
+            }
+            Event::PlayerStateChanged(change) if matches!(change.state, PlayerState::Finished | PlayerState::Paused) => { break; }
+            _ => {}
+        }
+    }
+    let _ = cmd_sender.send(Command::Quit);
+    errors
+}
@@ -12,9 +12,11 @@ use ratatui::style::{Color, Style};
 use ratatui::{Frame, Terminal};
 use ratatui::text::{Span, Line};
 use ratatui::widgets::{Block, Borders, Cell, Gauge, Paragraph, Row, Table};
+use log::error;
 use tui_logger::TuiLoggerWidget;
 
-use packet_play::{Event, Command, PlayerOptions, PlayerError, PlayerState, PositionChange};
+use pcap_files::BpfFilter;
+use packet_play::{Event, Command, IterationChange, PacingReport, PlaybackSpeed, PlayerOptions, PlayerError, PlayerState, PositionChange};
 use packet_rehash_core::utils::format::FormattedDuration;
 use crate::actions::Action;
 
@@ -26,6 +28,8 @@ struct App {
     options: PlayerOptions,
     current_state : PlayerState,
     current_position : PositionChange,
+    current_iteration : IterationChange,
+    current_pacing : PacingReport,
     cmd_sender : Sender<Command>,
     event_receiver: Receiver<Event>,
     input_handler : InputHandler,
@@ -44,6 +48,11 @@ impl App {
             Action::Quit => { self.handle_quit() }
             Action::PlayPause => { self.handle_playpause() }
             Action::Rewind => { self.handle_rewind() }
+            Action::SpeedUp => { self.handle_speed(PlaybackSpeed::faster) }
+            Action::SpeedDown => { self.handle_speed(PlaybackSpeed::slower) }
+            Action::Unthrottled => { Some(Command::SetUnthrottled) }
+            Action::StepForward => { Some(Command::StepForward(1)) }
+            Action::StepBackward => { Some(Command::StepBackward(1)) }
             Action::CycleArea => { self.handle_cycle_area() }
         };
         if let Some(command) = command {
@@ -63,7 +72,7 @@ impl App {
 
     fn handle_right(&mut self) -> Option<Command> {
         if self.selected_button < BUTTONS.len() - 1 {
-             self.selected_button = self.selected_button + 1
+             self.selected_button += 1
         };
         None
     }
@@ -87,6 +96,16 @@ impl App {
         } else { None }
     }
 
+    /// Steps the speed, or returns to the last multiplier when unthrottled.
+    fn handle_speed(&mut self, step: fn(&PlaybackSpeed) -> f64) -> Option<Command> {
+        let speed = match self.current_position.speed {
+            PlaybackSpeed::Multiplier(_) => { step(&self.current_position.speed) }
+            PlaybackSpeed::Unthrottled => { self.options.speed }
+        };
+        self.options.speed = speed;
+        Some(Command::SetSpeed(speed))
+    }
+
     fn handle_rewind(&self) -> Option<Command> {
         Some(Command::Rewind)
     }
@@ -109,6 +128,8 @@ pub(crate) fn run_tui(options: PlayerOptions, event_receiver: Receiver<Event>, c
         options,
         current_state: PlayerState::Initial,
         current_position: Default::default(),
+        current_iteration: Default::default(),
+        current_pacing: Default::default(),
         cmd_sender,
         event_receiver,
         input_handler,
@@ -131,7 +152,9 @@ fn gui_loop(mut app: App,
         // Read all events currently in the event channel
         for event in app.event_receiver.try_iter() {
             match event {
-                Event::Error(_) => {}
+                Event::Error(error) => {
+                    error!("{error}");
+                }
                 Event::PlayerReady => {}
                 Event::PlayerStateChanged(new_state) => {
                     app.current_state = new_state.state;
@@ -139,6 +162,12 @@ fn gui_loop(mut app: App,
                 Event::PlayerPositionChanged(new_position) => {
                     app.current_position = new_position;
                 }
+                Event::PlayerIterationChanged(new_iteration) => {
+                    app.current_iteration = new_iteration;
+                }
+                Event::PlayerPacingChanged(new_pacing) => {
+                    app.current_pacing = new_pacing;
+                }
                 Event::QuitCommanded => {
                     app.kill_signal = true;
                 }
@@ -156,7 +185,7 @@ fn gui_loop(mut app: App,
                 Input::Tick => {
                 }
             }
-            if let Err(_) = terminal.draw(|frame| draw(frame, &mut app)) {
+            if terminal.draw(|frame| draw(frame, &mut app)).is_err() {
                 app.kill_signal = true;
             }
         }
@@ -182,7 +211,7 @@ where B: Backend {
         )
         .split(size);
 
-    let header = draw_header(&app);
+    let header = draw_header(app);
     frame.render_widget(header, chunks[0]);
 
     let info_chunks = Layout::default()
@@ -193,13 +222,13 @@ where B: Backend {
         ].as_ref())
         .split(chunks[1]);
 
-    let recording_info = draw_info(&app);
+    let recording_info = draw_info(app);
     frame.render_widget(recording_info, info_chunks[0]);
 
     let help_info = draw_help();
     frame.render_widget(help_info, info_chunks[1]);
 
-    let progress_bar = draw_progress(&app);
+    let progress_bar = draw_progress(app);
     frame.render_widget(progress_bar, chunks[2]);
 
     let buttons_chunks = Layout::default()
@@ -219,7 +248,7 @@ where B: Backend {
         .borders(Borders::ALL);
     frame.render_widget(control_block, chunks[3]);
     BUTTONS.iter().enumerate().map(|(i, btn_text)| {
-        (i, Paragraph::new(btn_text.clone()).alignment(Alignment::Center).style(
+        (i, Paragraph::new(*btn_text).alignment(Alignment::Center).style(
             if app.selected_button == i {
                 Style::default().fg(Color::Blue).bg(Color::White)
             } else {
@@ -232,7 +261,7 @@ where B: Backend {
     frame.render_widget(logs, chunks[4]);
 }
 
-fn draw_header(app: &App) -> Paragraph {
+fn draw_header(app: &App) -> Paragraph<'_> {
     Paragraph::new(
         vec![
             Line::from(Span::styled("Packet Play", Style::default().fg(Color::White))),
@@ -243,11 +272,9 @@ fn draw_header(app: &App) -> Paragraph {
         .block(Block::default().borders(Borders::ALL))
 }
 
-fn draw_info(app: &App) -> Table {
+fn draw_info(app: &App) -> Table<'_> {
     let file_name = if let Some(os_str) = Path::new(&app.options.file).file_name() {
-        if let Some(name) = os_str.to_str() {
-            name
-        } else { "<incorrect OsStr>" }
+        os_str.to_str().unwrap_or("<incorrect OsStr>")
     } else { "<incorrect path>" };
     let info_key_style = Style::default().fg(Color::Gray);
     let info_value_style = Style::default().fg(Color::White);
@@ -260,6 +287,22 @@ fn draw_info(app: &App) -> Table {
             Cell::from(Span::styled("Destination:", info_key_style)),
             Cell::from(Span::styled(app.options.destination.to_string(), info_value_style)),
         ]),
+        Row::new(vec![
+            Cell::from(Span::styled("Mode:", info_key_style)),
+            Cell::from(Span::styled(app.options.destination_mode.to_string(), info_value_style)),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::styled("Filter:", info_key_style)),
+            Cell::from(Span::styled(app.options.filter.clone().unwrap_or_default().to_string(), info_value_style)),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::styled("BPF filter:", info_key_style)),
+            Cell::from(Span::styled(app.options.bpf.as_ref().map_or("-", BpfFilter::expression), info_value_style)),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::styled("Bind address:", info_key_style)),
+            Cell::from(Span::styled(app.options.bind_address.as_ref().map_or("any".to_string(), |address| address.to_string()), info_value_style)),
+        ]),
         Row::new(vec![
             Cell::from(Span::styled("Source port:", info_key_style)),
             Cell::from(Span::styled(app.options.source_port.to_string(), info_value_style)),
@@ -268,6 +311,14 @@ fn draw_info(app: &App) -> Table {
             Cell::from(Span::styled("TTL:", info_key_style)),
             Cell::from(Span::styled(app.options.ttl.to_string(), info_value_style)),
         ]),
+        Row::new(vec![
+            Cell::from(Span::styled("Multicast:", info_key_style)),
+            Cell::from(Span::styled(app.options.multicast().to_string(), info_value_style)),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::styled("Raw interface:", info_key_style)),
+            Cell::from(Span::styled(app.options.raw.as_deref().unwrap_or("-"), info_value_style)),
+        ]),
         Row::new(vec![
             Cell::from(Span::styled("Auto play:", info_key_style)),
             Cell::from(Span::styled((!app.options.auto_play_disable).to_string(), info_value_style)),
@@ -280,6 +331,18 @@ fn draw_info(app: &App) -> Table {
             Cell::from(Span::styled("State:", info_key_style)),
             Cell::from(Span::styled(app.current_state.to_string(), info_value_style)),
         ]),
+        Row::new(vec![
+            Cell::from(Span::styled("Speed:", info_key_style)),
+            Cell::from(Span::styled(app.current_position.speed.to_string(), info_value_style)),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::styled("Timing:", info_key_style)),
+            Cell::from(Span::styled(app.current_pacing.to_string(), info_value_style)),
+        ]),
+        Row::new(vec![
+            Cell::from(Span::styled("Iteration:", info_key_style)),
+            Cell::from(Span::styled(format!("{} / {}", app.current_iteration.iteration, app.current_iteration.repetitions), info_value_style)),
+        ]),
         Row::new(vec![
             Cell::from(Span::styled("Packets:", info_key_style)),
             Cell::from(Span::styled(format!("{} / {}",app.current_position.position, app.current_position.max_position), info_value_style)),
@@ -328,7 +391,7 @@ fn draw_help() -> Table<'static> {
         .column_spacing(1)
 }
 
-fn draw_progress(app: &App) -> Gauge {
+fn draw_progress(app: &App) -> Gauge<'_> {
     let progress_percent = (app.current_position.time_position.as_secs() * 100)
         .checked_div(app.current_position.time_total.as_secs()).unwrap_or(0);
 
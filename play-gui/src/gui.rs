@@ -4,8 +4,8 @@ use eframe::NativeOptions;
 use egui::Button;
 use log::{trace};
 use crate::{PlayerOptions};
-use packet_play::{Command, Event, PositionChange};
-use packet_play::{PlayerError, PlayerState, PLAYER_STARTUP_TIMEOUT_MS};
+use packet_play::{Command, Event, IterationChange, PacingReport, PositionChange, Repetitions};
+use packet_play::{PlaybackSpeed, PlayerError, PlayerState, PLAYER_STARTUP_TIMEOUT_MS, SPEED_STEPS};
 use packet_rehash_core::utils::format::FormattedDuration;
 
 pub(crate) fn run_gui(options: PlayerOptions, event_receiver: Receiver<Event>, cmd_sender: Sender<Command>) -> Result<(), PlayerError> {
@@ -38,17 +38,19 @@ pub(crate) fn run_gui(options: PlayerOptions, event_receiver: Receiver<Event>, c
             GuiApp::new(options, cmd_sender, event_receiver)))
     );
 
-    if let Err(_) = result {
+    if result.is_err() {
         return Err(PlayerError::PlayerInitError)
     };
 
-    return Ok(())
+    Ok(())
 }
 
 struct GuiApp {
     options: PlayerOptions,
     current_state : PlayerState,
     current_position : PositionChange,
+    current_iteration : IterationChange,
+    current_pacing : PacingReport,
     cmd_sender: Sender<Command>,
     event_receiver: Receiver<Event>,
 }
@@ -59,6 +61,8 @@ impl GuiApp {
             options,
             current_state: PlayerState::Initial,
             current_position: Default::default(),
+            current_iteration: Default::default(),
+            current_pacing: Default::default(),
             cmd_sender,
             event_receiver,
         }
@@ -78,6 +82,14 @@ impl eframe::App for GuiApp {
                 self.current_position = position;
                 None
             }
+            Ok(Event::PlayerIterationChanged(iteration)) => {
+                self.current_iteration = iteration;
+                None
+            }
+            Ok(Event::PlayerPacingChanged(pacing)) => {
+                self.current_pacing = pacing;
+                None
+            }
             Ok(Event::Error(error)) => { Some(format!("{error:?}")) }
             Err(TryRecvError::Empty) => { None }
             Err(TryRecvError::Disconnected) => {
@@ -98,6 +110,10 @@ impl eframe::App for GuiApp {
             });
             ui.label(format!("Packets: [{}/{}]", self.current_position.position, self.current_position.max_position));
             ui.label(format!("Time: [ {} / {} ]", FormattedDuration::new(self.current_position.time_position), FormattedDuration::new(self.current_position.time_total)));
+            ui.label(format!("Timing: {}", self.current_pacing));
+            if self.current_iteration.repetitions != Repetitions::Once {
+                ui.label(format!("Iteration: [{}/{}]", self.current_iteration.iteration, self.current_iteration.repetitions));
+            }
 
             ui.horizontal(|ui| {
                 if ui.add_enabled(
@@ -115,6 +131,36 @@ impl eframe::App for GuiApp {
                     Button::new("Rewind")).clicked() {
                     let _ = self.cmd_sender.send(Command::Rewind);
                 }
+                let stepping = self.current_state != PlayerState::Playing;
+                if ui.add_enabled(stepping, Button::new("Step Back")).clicked() {
+                    let _ = self.cmd_sender.send(Command::StepBackward(1));
+                }
+                if ui.add_enabled(stepping, Button::new("Step")).clicked() {
+                    let _ = self.cmd_sender.send(Command::StepForward(1));
+                }
+            });
+            ui.horizontal(|ui| {
+                let unthrottled = self.current_position.speed == PlaybackSpeed::Unthrottled;
+                let mut speed = match self.current_position.speed {
+                    PlaybackSpeed::Multiplier(multiplier) => { multiplier }
+                    PlaybackSpeed::Unthrottled => { self.options.speed }
+                };
+                let slider = egui::Slider::new(&mut speed, SPEED_STEPS[0]..=SPEED_STEPS[SPEED_STEPS.len() - 1])
+                    .logarithmic(true)
+                    .suffix("x")
+                    .text("Speed");
+                if ui.add_enabled(!unthrottled, slider).changed() {
+                    self.options.speed = speed;
+                    let _ = self.cmd_sender.send(Command::SetSpeed(speed));
+                }
+                let mut unthrottled_checked = unthrottled;
+                if ui.checkbox(&mut unthrottled_checked, "Unthrottled").changed() {
+                    let _ = self.cmd_sender.send(if unthrottled_checked {
+                        Command::SetUnthrottled
+                    } else {
+                        Command::SetSpeed(self.options.speed)
+                    });
+                }
             });
             ui.label(message.unwrap_or("".to_string()));
         });
@@ -123,9 +169,9 @@ impl eframe::App for GuiApp {
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
         // Send Quit command to Player
         let _ = self.cmd_sender.send(Command::Quit);
-        // Wait for player to shutdown
+        // Wait for player to shutdown, unless it already stopped
         loop {
-            if let Ok(Event::QuitCommanded) = self.event_receiver.try_recv() {
+            if let Ok(Event::QuitCommanded) | Err(TryRecvError::Disconnected) = self.event_receiver.try_recv() {
                 break;
             }
         }
@@ -135,7 +181,7 @@ impl eframe::App for GuiApp {
 fn window_options() -> NativeOptions {
     NativeOptions {
         decorated: true,
-        initial_window_size: Some(egui::Vec2::new(500f32,150f32)),
+        initial_window_size: Some(egui::Vec2::new(500f32,200f32)),
         ..Default::default()
     }
 }
\ No newline at end of file
@@ -7,7 +7,7 @@ use std::sync::mpsc;
 use clap::Parser;
 
 use log::error;
-use packet_play::{Player, PlayerOptions, Recording};
+use packet_play::{file_sink, raw_sink, Player, PlayerOptions, Recording};
 
 pub(crate) const ERROR_CANNOT_START : i32 = 1;
 pub(crate) const ERROR_RUNTIME : i32 = 2;
@@ -27,14 +27,40 @@ fn main() {
         let (event_sender, event_receiver) = mpsc::channel();
 
         // Spawn thread for the Player
-        let _player_handle = Player::builder()
+        let mut player_builder = Player::builder()
             .recording(recording)
             .destination(options.destination)
+            .destination_mode(options.destination_mode)
+            .mappings(options.mappings.clone())
+            .filter(options.filter.clone().unwrap_or_default())
+            .bpf(options.bpf.clone())
+            .bind_address(options.bind_address.clone())
             .source_port(options.source_port)
             .ttl(options.ttl)
+            .multicast(options.multicast())
+            .speed(options.playback_speed())
+            .repeat(options.repeat())
+            .send_failure(options.send_failure)
             .cmd_rx(cmd_receiver)
-            .event_tx(event_sender)
-            .build().expect("Failed to initialise Player.");
+            .event_tx(event_sender);
+        if let Some(output) = &options.output {
+            match file_sink(output) {
+                Ok(sink) => { player_builder = player_builder.sink(sink).simulate(options.output_start); }
+                Err(error) => {
+                    eprintln!("Cannot write to {output}, because: {error}");
+                    exit(ERROR_CANNOT_START);
+                }
+            }
+        } else if let Some(interface) = &options.raw {
+            match raw_sink(interface, &options.rewrites, options.recalculate_checksums) {
+                Ok(sink) => { player_builder = player_builder.sink(sink); }
+                Err(error) => {
+                    eprintln!("Cannot replay on interface {interface}, because: {error}");
+                    exit(ERROR_CANNOT_START);
+                }
+            }
+        }
+        let _player_handle = player_builder.build().expect("Failed to initialise Player.");
 
         // Start the gui
         if let Err(error) = gui::run_gui(options, event_receiver, cmd_sender) {
@@ -43,7 +69,7 @@ fn main() {
         }
     } else {
         let error = recording.unwrap_err();
-        error!("Cannot play recording, because: {:?}", error);
+        error!("Cannot play recording, because: {error}");
         exit(ERROR_CANNOT_START);
     };
 }
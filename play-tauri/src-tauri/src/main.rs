@@ -4,25 +4,34 @@
 )]
 
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
 use std::sync::{Arc, mpsc, Mutex, RwLock};
 use std::sync::mpsc::{Receiver, Sender};
 use std::thread;
 use std::thread::JoinHandle;
-use serde::{Serialize};
+use std::time::Duration;
+use serde::{Serialize, Serializer};
 use tauri::{Manager, Runtime, State, WindowEvent};
 use tauri::FileDropEvent::Dropped;
-use packet_play::{Command, defaults, Event, Player, Recording};
+use packet_play::{BindAddress, Command, defaults, PlayerError, DestinationMapping, DestinationMode, Event, MulticastInterface, MulticastOptions, Player, Recording};
 
 const MAIN_WINDOW_LABEL: &str = "main";
 
-#[derive(thiserror::Error, Debug, Serialize)]
+#[derive(thiserror::Error, Debug)]
 enum PlayError {
-    #[error("Error loading file.")]
-    CannotLoadFile,
+    #[error("Error loading file: {0}")]
+    CannotLoadFile(String),
     #[error("Incorrect player state for command {0}.")]
     IncorrectStateForCommand(String),
-    // #[error("Error updating settings.")]
-    // UpdateSettingsError
+    #[error("Invalid setting: {0}")]
+    InvalidSetting(String),
+}
+
+/// Errors are passed to the frontend as their message, to be shown in a notification.
+impl Serialize for PlayError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
 }
 
 struct SettingsWrapper {
@@ -32,8 +41,13 @@ struct SettingsWrapper {
 struct Settings {
     file: Option<String>,
     destination: SocketAddr,
+    destination_mode: DestinationMode,
+    mappings: Vec<DestinationMapping>,
+    bind_address: Option<BindAddress>,
     source_port: u16,
     ttl: u32,
+    multicast_interface: Option<MulticastInterface>,
+    multicast_loopback: bool,
 }
 
 impl Default for Settings {
@@ -43,8 +57,13 @@ impl Default for Settings {
             destination: SocketAddr::new(
                 IpAddr::V4(Ipv4Addr::BROADCAST),
                 defaults::DEFAULT_DEST_PORT),
+            destination_mode: DestinationMode::Fixed,
+            mappings: Vec::new(),
+            bind_address: None,
             source_port: defaults::DEFAULT_SRC_PORT,
-            ttl: defaults::DEFAULT_TTL
+            ttl: defaults::DEFAULT_TTL,
+            multicast_interface: None,
+            multicast_loopback: true,
         }
     }
 }
@@ -55,7 +74,7 @@ struct PlayerWrapper {
 
 #[derive(Debug)]
 struct PlayerHandle {
-    player_handle: JoinHandle<()>,
+    player_handle: JoinHandle<Result<(), PlayerError>>,
     cmd_sender: Arc<Mutex<Sender<Command>>>,
     event_receiver: Arc<Mutex<Receiver<Event>>>,
 }
@@ -68,8 +87,16 @@ impl PlayerHandle {
         let player_handle = Player::builder()
             .recording(recording)
             .destination(settings.destination)
+            .destination_mode(settings.destination_mode)
+            .mappings(settings.mappings.clone())
+            .bind_address(settings.bind_address.clone())
             .source_port(settings.source_port)
             .ttl(settings.ttl)
+            .multicast(MulticastOptions {
+                ttl: settings.ttl,
+                interface: settings.multicast_interface.clone(),
+                loopback: settings.multicast_loopback,
+            })
             .cmd_rx(cmd_receiver)
             .event_tx(event_sender)
             .build().unwrap();
@@ -119,7 +146,8 @@ fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             cmd_update_settings,
-            cmd_open, cmd_play, cmd_pause, cmd_rewind, cmd_seek])
+            cmd_open, cmd_play, cmd_pause, cmd_rewind, cmd_seek, cmd_seek_time,
+            cmd_step_forward, cmd_step_backward, cmd_set_speed, cmd_set_unthrottled])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -142,6 +170,12 @@ fn run_event_thread<R: Runtime>(receiver: Arc<Mutex<Receiver<Event>>>, window: t
                     Event::PlayerPositionChanged(position_update) => {
                         let _ = window.emit_all("player_event_position", position_update).unwrap();
                     }
+                    Event::PlayerIterationChanged(iteration_update) => {
+                        let _ = window.emit_all("player_event_iteration", iteration_update).unwrap();
+                    }
+                    Event::PlayerPacingChanged(pacing_update) => {
+                        let _ = window.emit_all("player_event_pacing", pacing_update).unwrap();
+                    }
                     Event::QuitCommanded => {
                         let _ = window.emit_all("player_event_quit", "").unwrap();
                         return;
@@ -191,25 +225,45 @@ fn open_file<R: Runtime>(window: tauri::Window<R>,
 
             Ok(())
         }
-        Err(_err) => {
-            return Err(PlayError::CannotLoadFile)
+        Err(err) => {
+            return Err(PlayError::CannotLoadFile(err.to_string()))
         }
     }
 }
 
 #[tauri::command]
 fn cmd_update_settings(settings_state: State<SettingsWrapper>,
-                       destination: &str, source_port: u16, ttl: u32) -> Result<(), PlayError> {
+                       destination: &str, bind_address: &str, source_port: u16, ttl: u32,
+                       multicast_interface: &str, multicast_loopback: bool) -> Result<(), PlayError> {
+    let destination = destination.parse()
+        .map_err(|_| PlayError::InvalidSetting(format!("`{destination}` is not a destination address")))?;
+    let bind_address = optional_setting(bind_address)?;
+    let multicast_interface = optional_setting(multicast_interface)?;
+
     let mut settings = settings_state.settings.write().unwrap();
     *settings = Settings {
         file: settings.file.clone(),
-        destination: destination.parse().expect("Failed to parse socket address"),
+        destination,
+        destination_mode: settings.destination_mode,
+        mappings: settings.mappings.clone(),
+        bind_address,
         source_port,
-        ttl
+        ttl,
+        multicast_interface,
+        multicast_loopback,
     };
     Ok(())
 }
 
+/// Parses a setting that is left out when empty.
+fn optional_setting<T: FromStr<Err = String>>(value: &str) -> Result<Option<T>, PlayError> {
+    if value.trim().is_empty() {
+        Ok(None)
+    } else {
+        value.parse().map(Some).map_err(PlayError::InvalidSetting)
+    }
+}
+
 #[tauri::command]
 fn cmd_open(window: tauri::Window,
             settings_state: State<SettingsWrapper>,
@@ -262,4 +316,63 @@ fn cmd_seek(player_state: State<PlayerWrapper>, to_position: usize) -> Result<()
     } else {
         Err(PlayError::IncorrectStateForCommand(format!("{}", seek_cmd)))
     }
-}
\ No newline at end of file
+}
+
+#[tauri::command]
+fn cmd_seek_time(player_state: State<PlayerWrapper>, seconds: f64) -> Result<(), PlayError> {
+    let handle = player_state.player.read().unwrap();
+    let seek_cmd = Command::SeekTime(Duration::try_from_secs_f64(seconds).unwrap_or_default());
+    if let Some(handle) = &*handle {
+        let _ = &handle.cmd_sender.lock().unwrap().send(seek_cmd);
+        Ok(())
+    } else {
+        Err(PlayError::IncorrectStateForCommand(format!("{}", seek_cmd)))
+    }
+}
+
+#[tauri::command]
+fn cmd_step_forward(player_state: State<PlayerWrapper>, steps: usize) -> Result<(), PlayError> {
+    let handle = player_state.player.read().unwrap();
+    let step_cmd = Command::StepForward(steps);
+    if let Some(handle) = &*handle {
+        let _ = &handle.cmd_sender.lock().unwrap().send(step_cmd);
+        Ok(())
+    } else {
+        Err(PlayError::IncorrectStateForCommand(format!("{}", step_cmd)))
+    }
+}
+
+#[tauri::command]
+fn cmd_step_backward(player_state: State<PlayerWrapper>, steps: usize) -> Result<(), PlayError> {
+    let handle = player_state.player.read().unwrap();
+    let step_cmd = Command::StepBackward(steps);
+    if let Some(handle) = &*handle {
+        let _ = &handle.cmd_sender.lock().unwrap().send(step_cmd);
+        Ok(())
+    } else {
+        Err(PlayError::IncorrectStateForCommand(format!("{}", step_cmd)))
+    }
+}
+
+#[tauri::command]
+fn cmd_set_speed(player_state: State<PlayerWrapper>, speed: f64) -> Result<(), PlayError> {
+    let handle = player_state.player.read().unwrap();
+    let speed_cmd = Command::SetSpeed(speed);
+    if let Some(handle) = &*handle {
+        let _ = &handle.cmd_sender.lock().unwrap().send(speed_cmd);
+        Ok(())
+    } else {
+        Err(PlayError::IncorrectStateForCommand(format!("{}", speed_cmd)))
+    }
+}
+
+#[tauri::command]
+fn cmd_set_unthrottled(player_state: State<PlayerWrapper>) -> Result<(), PlayError> {
+    let handle = player_state.player.read().unwrap();
+    if let Some(handle) = &*handle {
+        let _ = &handle.cmd_sender.lock().unwrap().send(Command::SetUnthrottled);
+        Ok(())
+    } else {
+        Err(PlayError::IncorrectStateForCommand(format!("{}", Command::SetUnthrottled)))
+    }
+}
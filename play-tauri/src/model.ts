@@ -1,7 +1,10 @@
 interface Settings {
     destination: string,
+    bind_address: string,
     source_port: number,
     ttl: number,
+    multicast_interface: string,
+    multicast_loopback: boolean,
 }
 
 interface PlayerPosition {